@@ -0,0 +1,193 @@
+#![allow(dead_code)]
+
+use std::io;
+use std::fs;
+use std::fmt;
+use std::path::Path;
+use std::result::Result;
+
+use md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde::de::{Deserialize, Deserializer, self};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HashAlgo {
+    Sha1,
+    Sha256,
+    Md5,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checksum {
+    algo: HashAlgo,
+    value: String,
+    // weaker hashes listed next to the preferred one, kept so that serializing loses nothing
+    others: Vec<(HashAlgo, String)>,
+}
+
+pub enum Hasher {
+    Sha1(Sha1),
+    Sha256(Sha256),
+    Md5(md5::Context),
+}
+
+#[derive(Deserialize)]
+struct ChecksumFields {
+    sha1: Option<String>,
+    sha256: Option<String>,
+    md5: Option<String>,
+}
+
+impl HashAlgo {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            HashAlgo::Sha1 => "sha1",
+            HashAlgo::Sha256 => "sha256",
+            HashAlgo::Md5 => "md5",
+        }
+    }
+}
+
+impl fmt::Display for HashAlgo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Checksum {
+    pub fn new(algo: HashAlgo, value: String) -> Checksum {
+        Checksum { algo, value, others: Vec::new() }
+    }
+
+    pub fn sha1(value: String) -> Checksum {
+        Checksum::new(HashAlgo::Sha1, value)
+    }
+
+    pub fn algo(&self) -> HashAlgo {
+        self.algo
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn get(&self, algo: HashAlgo) -> Option<&str> {
+        if self.algo == algo { return Some(&self.value); }
+        self.others.iter().find(|&&(a, _)| a == algo).map(|(_, value)| value.as_str())
+    }
+
+    pub fn hasher(&self) -> Hasher {
+        Hasher::new(self.algo)
+    }
+
+    pub fn matches(&self, digest: &str) -> bool {
+        self.value.eq_ignore_ascii_case(digest)
+    }
+
+    pub fn verify_file(&self, path: &Path) -> io::Result<bool> {
+        Result::Ok(self.matches(&hash_file(path, self.algo)?))
+    }
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.algo, self.value)
+    }
+}
+
+impl Serialize for Checksum {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1 + self.others.len()))?;
+        map.serialize_entry(self.algo.name(), &self.value)?;
+        for &(algo, ref value) in self.others.iter() {
+            map.serialize_entry(algo.name(), value)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Checksum {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // prefer the strongest hash when a provider lists several
+        let ChecksumFields { sha1, sha256, md5 } = ChecksumFields::deserialize(deserializer)?;
+        let mut present = vec![(HashAlgo::Sha256, sha256), (HashAlgo::Sha1, sha1), (HashAlgo::Md5, md5)]
+            .into_iter().filter_map(|(algo, value)| value.map(|value| (algo, value)));
+        match present.next() {
+            Some((algo, value)) => Result::Ok(Checksum { algo, value, others: present.collect() }),
+            None => Result::Err(de::Error::missing_field("sha1"))
+        }
+    }
+}
+
+impl Hasher {
+    pub fn new(algo: HashAlgo) -> Hasher {
+        match algo {
+            HashAlgo::Sha1 => Hasher::Sha1(Sha1::new()),
+            HashAlgo::Sha256 => Hasher::Sha256(Sha256::new()),
+            HashAlgo::Md5 => Hasher::Md5(md5::Context::new()),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match *self {
+            Hasher::Sha1(ref mut hasher) => hasher.update(data),
+            Hasher::Sha256(ref mut hasher) => hasher.update(data),
+            Hasher::Md5(ref mut hasher) => hasher.consume(data),
+        }
+    }
+
+    pub fn hex_digest(self) -> String {
+        match self {
+            Hasher::Sha1(hasher) => hasher.digest().to_string(),
+            Hasher::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+            Hasher::Md5(hasher) => format!("{:x}", hasher.compute()),
+        }
+    }
+}
+
+pub fn hash_file(path: &Path, algo: HashAlgo) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Hasher::new(algo);
+    let mut buffer = [0u8; 8192];
+    loop {
+        match io::Read::read(&mut file, &mut buffer)? {
+            0 => break,
+            n => hasher.update(&buffer[..n])
+        }
+    }
+    Result::Ok(hasher.hex_digest())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest(algo: HashAlgo, data: &[u8]) -> String {
+        let mut hasher = Hasher::new(algo);
+        hasher.update(data);
+        hasher.hex_digest()
+    }
+
+    #[test]
+    fn hash_algorithms() {
+        assert_eq!(digest(HashAlgo::Sha256, b""), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(digest(HashAlgo::Sha256, b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(digest(HashAlgo::Sha256, &[b'a'; 1000]), "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3");
+        assert_eq!(digest(HashAlgo::Sha1, b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(digest(HashAlgo::Md5, b"abc"), "900150983cd24fb0d6963f7d28e17f72");
+    }
+
+    #[test]
+    fn deserialize_checksum() {
+        let checksum: Checksum = ::serde_json::from_str(r#"{ "sha1": "AB", "sha256": "cd", "size": 1 }"#).unwrap();
+        assert_eq!((checksum.algo(), checksum.value()), (HashAlgo::Sha256, "cd"));
+        assert_eq!(checksum.get(HashAlgo::Sha1), Some("AB"));
+        assert!(checksum.matches("CD"));
+        let json = ::serde_json::to_value(&checksum).unwrap();
+        assert_eq!(json, json!({ "sha1": "AB", "sha256": "cd" }));
+        assert_eq!(::serde_json::from_value::<Checksum>(json).unwrap(), checksum);
+        assert!(::serde_json::from_str::<Checksum>(r#"{ "size": 1 }"#).is_err());
+    }
+}
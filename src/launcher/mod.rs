@@ -1,24 +1,63 @@
 #![allow(dead_code)]
 
+use std::io;
+use std::fs;
+use std::env;
+use std::fmt;
 use std::path;
+use std::error;
+use std::thread;
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex};
 use std::result::Result;
-use std::collections::HashMap;
-use std::process::{Child, Command};
+use std::net::SocketAddr;
+use std::collections::{HashMap, HashSet};
+use std::process::{Child, Command, Stdio};
 
+use serde_json;
+
+use net;
 use parsing;
+use requests;
 use versions;
 use yggdrasil;
 
-#[derive(Debug)]
+const MICROSOFT_ONLY_OPTIONS: &[&str] = &["--xuid", "--clientId"];
+
+const JRE_COMMAND_TIMEOUT_MILLIS: u64 = 3000;
+
+// picked up by every jvm, so an inherited -Xmx here silently overrides the launcher's own
+const JAVA_OPTIONS_VARIABLES: &[&str] = &["JAVA_TOOL_OPTIONS", "_JAVA_OPTIONS", "JDK_JAVA_OPTIONS"];
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct JvmOption(String);
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct GameOption(String, Option<String>);
 
+#[derive(Clone, Debug)]
+pub enum JvmProfile {
+    Vanilla,
+    Modded,
+    Aikar,
+    Custom(Vec<String>),
+}
+
+#[derive(Debug)]
+pub enum BuildError {
+    MissingRootDir,
+    MissingAuth,
+    NoJreFound,
+    NoJreForMajor(u32),
+}
+
 #[derive(Default)]
 pub struct MinecraftLauncherBuilder {
     program_path: Option<String>,
+    jre_major_version: Option<u32>,
+    allow_newer_jre: Option<bool>,
     game_root_dir: Option<path::PathBuf>,
+    game_dir: Option<path::PathBuf>,
     assets_dir: Option<path::PathBuf>,
     libraries_dir: Option<path::PathBuf>,
     launcher_name_version: Option<(String, String)>,
@@ -26,11 +65,30 @@ pub struct MinecraftLauncherBuilder {
     min_memory_mib: Option<f32>,
     max_memory_mib: Option<f32>,
     window_resolution: Option<(u32, u32)>,
+    fullscreen: Option<bool>,
+    jvm_profile: Option<JvmProfile>,
+    extract_excludes: Option<Vec<String>>,
+    extra_tokens: Option<HashMap<String, String>>,
+    extra_classpath: Option<Vec<path::PathBuf>>,
+    extra_jvm_args: Option<Vec<String>>,
+    system_properties: Option<HashMap<String, String>>,
+    features: Option<versions::FeatureSet>,
+    user_type: Option<yggdrasil::UserType>,
+    version_type_override: Option<String>,
+    server: Option<(String, Option<u16>)>,
+    natives_dir: Option<path::PathBuf>,
+    auto_extract_natives: Option<bool>,
+    log_file: Option<(path::PathBuf, bool)>,
+    wrapper: Option<(String, Vec<String>)>,
+    clear_java_options: Option<bool>,
+    request_client: Option<requests::RequestClient>,
+    offline: Option<bool>,
 }
 
 pub struct MinecraftLauncher {
     program_path: String,
     game_root_dir: path::PathBuf,
+    game_dir: path::PathBuf,
     assets_dir: path::PathBuf,
     libraries_dir: path::PathBuf,
     manager: versions::VersionManager,
@@ -38,6 +96,23 @@ pub struct MinecraftLauncher {
     auth_info: yggdrasil::AuthInfo,
     min_max_memory_mib: (f32, f32),
     window_resolution: (u32, u32),
+    fullscreen: bool,
+    jvm_profile: JvmProfile,
+    extract_excludes: Vec<String>,
+    extra_tokens: HashMap<String, String>,
+    extra_classpath: Vec<path::PathBuf>,
+    extra_jvm_args: Vec<String>,
+    system_properties: HashMap<String, String>,
+    features: versions::FeatureSet,
+    user_type: Option<yggdrasil::UserType>,
+    version_type_override: Option<String>,
+    server: Option<(String, Option<u16>)>,
+    natives_dir: Option<path::PathBuf>,
+    auto_extract_natives: bool,
+    log_file: Option<(path::PathBuf, bool)>,
+    wrapper: Option<(String, Vec<String>)>,
+    clear_java_options: bool,
+    offline: bool,
 }
 
 #[derive(Debug)]
@@ -48,6 +123,28 @@ pub struct LaunchArguments {
     game_options: Vec<GameOption>,
     game_native_path: path::PathBuf,
     game_natives: versions::NativeCollection,
+    auto_extract_natives: bool,
+    log_file: Option<(path::PathBuf, bool)>,
+    wrapper: Option<(String, Vec<String>)>,
+    clear_java_options: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LaunchSpec {
+    program: String,
+    main_class: String,
+    jvm_options: Vec<JvmOption>,
+    game_options: Vec<GameOption>,
+    natives_directory: path::PathBuf,
+    natives: Vec<versions::NativeJar>,
+    #[serde(default = "default_auto_extract_natives")]
+    auto_extract_natives: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    log_file: Option<(path::PathBuf, bool)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    wrapper: Option<(String, Vec<String>)>,
+    #[serde(default = "default_clear_java_options")]
+    clear_java_options: bool,
 }
 
 pub fn builder() -> MinecraftLauncherBuilder {
@@ -59,31 +156,206 @@ pub fn create(game_dir: path::PathBuf,
     builder().root_dir(game_dir.as_path()).auth(game_auth_info).build()
 }
 
-#[cfg(target_os = "windows")]
 pub fn find_jre() -> Vec<String> {
-    Vec::new() // TODO
+    sort_jre_candidates(jre_candidates())
+}
+
+#[cfg(target_os = "windows")]
+fn jre_candidates() -> Vec<path::PathBuf> {
+    env::var_os("JAVA_HOME").map(|java_home| path::PathBuf::from(java_home).join("bin\\java.exe")).into_iter().collect()
 }
 
 #[cfg(target_os = "macos")]
-pub fn find_jre() -> Vec<String> {
-    Vec::new() // TODO: I cannot afford a mac
+fn jre_candidates() -> Vec<path::PathBuf> {
+    let mut candidates = Vec::new();
+    if let Result::Ok(entries) = fs::read_dir("/Library/Java/JavaVirtualMachines") {
+        candidates.extend(entries.filter_map(|entry| entry.ok()).map(|entry| entry.path().join("Contents/Home/bin/java")));
+    }
+    candidates.extend(env::var_os("JAVA_HOME").map(|java_home| path::PathBuf::from(java_home).join("bin/java")));
+    candidates
 }
 
 #[cfg(target_os = "linux")]
-pub fn find_jre() -> Vec<String> {
-    let program = "update-alternatives";
-    if let Result::Ok(output) = Command::new(program).arg("--list").arg("java").output() {
-        if let Result::Ok(string) = String::from_utf8(output.stdout) {
-            return string.trim().split_whitespace().map(String::from).collect();
+fn jre_candidates() -> Vec<path::PathBuf> {
+    let timeout = JRE_COMMAND_TIMEOUT_MILLIS;
+    let mut candidates: Vec<path::PathBuf> = Vec::new();
+    let mut alternatives = Command::new("update-alternatives");
+    if let Some(string) = output_with_timeout(alternatives.arg("--list").arg("java"), timeout) {
+        candidates.extend(string.split_whitespace().map(path::PathBuf::from));
+    }
+    candidates.extend(java_binaries_under(path::Path::new("/usr/lib/jvm")));
+    if let Some(home) = env::var_os("HOME").map(path::PathBuf::from) {
+        candidates.extend(java_binaries_under(home.join(".sdkman/candidates/java").as_path()));
+        candidates.extend(java_binaries_under(home.join(".asdf/installs/java").as_path()));
+        candidates.push(home.join(".asdf/shims/java"));
+    }
+    if let Some(string) = output_with_timeout(Command::new("which").arg("java"), timeout) {
+        candidates.extend(string.lines().next().map(|line| path::PathBuf::from(line.trim())));
+    }
+    candidates.extend(env::var_os("JAVA_HOME").map(|java_home| path::PathBuf::from(java_home).join("bin/java")));
+    candidates
+}
+
+// newest last since callers pick the last entry as the default, ties keep the discovery order
+fn sort_jre_candidates(candidates: Vec<path::PathBuf>) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut result: Vec<(String, Option<u32>)> = candidates.into_iter()
+        .filter_map(|candidate| fs::canonicalize(candidate).ok())
+        .filter(|candidate| is_executable(candidate.as_path()) && seen.insert(candidate.clone()))
+        .filter_map(|candidate| candidate.to_str().map(String::from))
+        .map(|program| {
+            let version = cached_jre_version(program.as_str());
+            (program, version)
+        }).collect();
+    result.sort_by_key(|&(_, version)| version);
+    result.into_iter().map(|(program, _)| program).collect()
+}
+
+#[cfg(unix)]
+fn is_executable(path: &path::Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0).unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &path::Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(target_os = "linux")]
+fn java_binaries_under(dir: &path::Path) -> Vec<path::PathBuf> {
+    let mut result: Vec<path::PathBuf> = match fs::read_dir(dir) {
+        Result::Ok(entries) => entries.filter_map(|entry| entry.ok()).map(|entry| entry.path().join("bin/java")).collect(),
+        Result::Err(_) => Vec::new()
+    };
+    result.sort();
+    result
+}
+
+#[cfg(target_os = "linux")]
+fn output_with_timeout(command: &mut Command, timeout_millis: u64) -> Option<String> {
+    use std::time::{Duration, Instant};
+    let mut child = command.stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::null()).spawn().ok()?;
+    let mut stdout = child.stdout.take()?;
+    // read on another thread so a chatty child cannot block on a full pipe
+    let reader = thread::spawn(move || {
+        let mut string = String::new();
+        io::Read::read_to_string(&mut stdout, &mut string).ok().map(|_| string)
+    });
+    let deadline = Instant::now() + Duration::from_millis(timeout_millis);
+    loop {
+        match child.try_wait() {
+            Result::Ok(Some(status)) => return if status.success() { reader.join().ok()? } else { None },
+            Result::Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(20)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
         }
     }
-    let program = "which";
-    if let Result::Ok(output) = Command::new(program).arg("java").output() {
-        if let Result::Ok(string) = String::from_utf8(output.stdout) {
-            return vec![String::from(string.trim())];
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BuildError::MissingRootDir => write!(f, "game root dir not specified"),
+            BuildError::MissingAuth => write!(f, "auth info not specified"),
+            BuildError::NoJreFound => write!(f, "jre not found"),
+            BuildError::NoJreForMajor(major) => write!(f, "no jre of major version {} found", major),
+        }
+    }
+}
+
+impl error::Error for BuildError {}
+
+thread_local! {
+    static JRE_VERSION_CACHE: RefCell<HashMap<String, Option<u32>>> = RefCell::new(HashMap::new());
+}
+
+pub fn find_jre_versions() -> Vec<(path::PathBuf, u32)> {
+    find_jre().into_iter().filter_map(|program| {
+        cached_jre_version(program.as_str()).map(|major| (path::PathBuf::from(program), major))
+    }).collect()
+}
+
+fn cached_jre_version(program: &str) -> Option<u32> {
+    JRE_VERSION_CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        *cache.entry(program.to_owned()).or_insert_with(|| probe_jre_version(program))
+    })
+}
+
+fn probe_jre_version(program: &str) -> Option<u32> {
+    let output = Command::new(program).arg("-version").output().ok()?;
+    let string = String::from_utf8_lossy(&output.stderr).into_owned();
+    let version = string.split('"').nth(1)?;
+    let mut parts = version.split(|c: char| !c.is_ascii_digit());
+    match parts.next()?.parse().ok()? {
+        1 => parts.next()?.parse().ok(),
+        major => Some(major)
+    }
+}
+
+fn find_jre_for_major(major: u32, allow_newer: bool) -> Option<String> {
+    select_jre_for_major(find_jre_versions(), major, allow_newer)
+}
+
+// a newer major is only picked on request, old versions often break on newer jres
+fn select_jre_for_major(mut candidates: Vec<(path::PathBuf, u32)>, major: u32, allow_newer: bool) -> Option<String> {
+    candidates.sort_by_key(|&(_, v)| v);
+    let exact = candidates.iter().find(|&&(_, v)| v == major);
+    let newer = candidates.iter().find(|&&(_, v)| allow_newer && v > major);
+    exact.or(newer).and_then(|(path, _)| path.to_str().map(String::from))
+}
+
+pub fn read_resolution_from_options(game_dir: &path::Path) -> Option<(u32, u32)> {
+    parse_options_resolution(&fs::read_to_string(game_dir.join("options.txt")).ok()?)
+}
+
+// older versions lack the keys and a zero means the game picks its own size
+fn parse_options_resolution(options: &str) -> Option<(u32, u32)> {
+    let (mut width, mut height) = (None, None);
+    for line in options.lines() {
+        let mut parts = line.splitn(2, ':');
+        match (parts.next().map(str::trim), parts.next().and_then(|v| v.trim().parse::<u32>().ok())) {
+            (Some("overrideWidth"), Some(value)) => width = Some(value),
+            (Some("overrideHeight"), Some(value)) => height = Some(value),
+            _ => ()
         }
     }
-    Vec::new()
+    match (width, height) {
+        (Some(width), Some(height)) if width > 0 && height > 0 => Some((width, height)),
+        _ => None
+    }
+}
+
+fn default_features() -> versions::FeatureSet {
+    versions::FeatureSet::new().with("has_custom_resolution", true)
+}
+
+fn default_auto_extract_natives() -> bool {
+    true
+}
+
+fn default_clear_java_options() -> bool {
+    true
+}
+
+fn tee_to_log<R, W>(mut source: R, mut target: W, log: Arc<Mutex<fs::File>>)
+    where R: io::Read + Send + 'static, W: io::Write + Send + 'static {
+    thread::spawn(move || {
+        let mut buffer = [0u8; 8192];
+        loop {
+            match source.read(&mut buffer) {
+                Result::Ok(0) | Result::Err(_) => break,
+                Result::Ok(n) => {
+                    let _ = target.write_all(&buffer[..n]);
+                    if let Result::Ok(mut file) = log.lock() { let _ = io::Write::write_all(&mut *file, &buffer[..n]); }
+                }
+            }
+        }
+    });
 }
 
 impl MinecraftLauncherBuilder {
@@ -92,6 +364,11 @@ impl MinecraftLauncherBuilder {
         self
     }
 
+    pub fn game_dir(mut self, dir: &path::Path) -> Self {
+        self.game_dir = Some(dir.to_path_buf());
+        self
+    }
+
     pub fn assets_dir(mut self, dir: &path::Path) -> Self {
         self.assets_dir = Some(dir.to_path_buf());
         self
@@ -107,6 +384,16 @@ impl MinecraftLauncherBuilder {
         self
     }
 
+    pub fn jre_for_major(mut self, major: u32) -> Self {
+        self.jre_major_version = Some(major);
+        self
+    }
+
+    pub fn allow_newer_jre(mut self, allow: bool) -> Self {
+        self.allow_newer_jre = Some(allow);
+        self
+    }
+
     pub fn auth(mut self, auth: yggdrasil::AuthInfo) -> Self {
         self.auth_info = Some(auth);
         self
@@ -132,55 +419,218 @@ impl MinecraftLauncherBuilder {
         self
     }
 
+    pub fn fullscreen(mut self, fullscreen: bool) -> Self {
+        self.fullscreen = Some(fullscreen);
+        self
+    }
+
+    pub fn jvm_profile(mut self, profile: JvmProfile) -> Self {
+        self.jvm_profile = Some(profile);
+        self
+    }
+
+    pub fn extract_excludes(mut self, excludes: Vec<String>) -> Self {
+        self.extract_excludes = Some(excludes);
+        self
+    }
+
+    pub fn extra_tokens(mut self, tokens: HashMap<String, String>) -> Self {
+        self.extra_tokens = Some(tokens);
+        self
+    }
+
+    pub fn extra_classpath(mut self, entries: Vec<path::PathBuf>) -> Self {
+        self.extra_classpath = Some(entries);
+        self
+    }
+
+    pub fn extra_jvm_args(mut self, args: Vec<String>) -> Self {
+        self.extra_jvm_args = Some(args);
+        self
+    }
+
+    pub fn system_properties(mut self, properties: HashMap<String, String>) -> Self {
+        self.system_properties = Some(properties);
+        self
+    }
+
+    pub fn feature(mut self, name: &str, value: bool) -> Self {
+        self.features = Some(self.features.take().unwrap_or_else(default_features).with(name, value));
+        self
+    }
+
+    pub fn user_type(mut self, user_type: yggdrasil::UserType) -> Self {
+        self.user_type = Some(user_type);
+        self
+    }
+
+    pub fn version_type_override(mut self, version_type: String) -> Self {
+        self.version_type_override = Some(version_type);
+        self
+    }
+
+    pub fn server(mut self, host: &str, port: Option<u16>) -> Self {
+        self.server = Some((host.to_owned(), port));
+        self
+    }
+
+    pub fn natives_dir(mut self, natives_dir: &path::Path) -> Self {
+        self.natives_dir = Some(natives_dir.to_path_buf());
+        self
+    }
+
+    pub fn auto_extract_natives(mut self, auto_extract: bool) -> Self {
+        self.auto_extract_natives = Some(auto_extract);
+        self
+    }
+
+    pub fn log_file(mut self, path: &path::Path, rotate: bool) -> Self {
+        self.log_file = Some((path.to_path_buf(), rotate));
+        self
+    }
+
+    pub fn wrapper(mut self, command: String, args: Vec<String>) -> Self {
+        self.wrapper = Some((command, args));
+        self
+    }
+
+    pub fn clear_java_options(mut self, clear: bool) -> Self {
+        self.clear_java_options = Some(clear);
+        self
+    }
+
+    pub fn request_client(mut self, client: requests::RequestClient) -> Self {
+        self.request_client = Some(client);
+        self
+    }
+
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = Some(offline);
+        self
+    }
+
     pub fn build(self) -> MinecraftLauncher {
-        let root_dir = self.game_root_dir.expect("game root dir not specified");
-        MinecraftLauncher {
-            program_path: self.program_path.unwrap_or_else(|| find_jre().pop().expect("jre not found")),
+        match self.try_build() {
+            Result::Ok(launcher) => launcher,
+            Result::Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn try_build(self) -> Result<MinecraftLauncher, BuildError> {
+        let root_dir = self.game_root_dir.ok_or(BuildError::MissingRootDir)?;
+        let auth_info = self.auth_info.ok_or(BuildError::MissingAuth)?;
+        let version_dir = root_dir.as_path().join("versions/");
+        let fullscreen = self.fullscreen.unwrap_or(false);
+        let mut features = self.features.unwrap_or_else(default_features);
+        // a fullscreen window has no size, so no version format should receive --width and --height
+        if fullscreen { features = features.with("has_custom_resolution", false) }
+        let program_path = match (self.program_path, self.jre_major_version) {
+            (Some(program_path), _) => program_path,
+            (None, Some(major)) => find_jre_for_major(major, self.allow_newer_jre.unwrap_or(false))
+                .ok_or(BuildError::NoJreForMajor(major))?,
+            (None, None) => find_jre().pop().ok_or(BuildError::NoJreFound)?,
+        };
+        let game_dir = self.game_dir.unwrap_or_else(|| root_dir.clone());
+        let window_resolution = self.window_resolution.or_else(|| read_resolution_from_options(game_dir.as_path()));
+        Result::Ok(MinecraftLauncher {
+            program_path,
             assets_dir: self.assets_dir.unwrap_or_else(|| root_dir.as_path().join("assets/")),
             libraries_dir: self.libraries_dir.unwrap_or_else(|| root_dir.as_path().join("libraries/")),
-            manager: versions::VersionManager::new(root_dir.as_path().join("versions/").as_path()),
+            manager: versions::VersionManager::with_absolute_root(version_dir.as_path())
+                .unwrap_or_else(|_| versions::VersionManager::new(version_dir.as_path()))
+                .with_request_client(self.request_client.unwrap_or_default()),
+            game_dir,
             game_root_dir: root_dir,
             launcher_name_version: self.launcher_name_version.unwrap_or(("RMCLL".to_owned(), "0.1.0".to_owned())),
-            auth_info: self.auth_info.expect("auth info not specified"),
+            auth_info,
             min_max_memory_mib: (self.min_memory_mib.unwrap_or(128f32), self.max_memory_mib.unwrap_or(0f32)),
-            window_resolution: self.window_resolution.unwrap_or((854, 480)),
-        }
+            window_resolution: window_resolution.unwrap_or((854, 480)),
+            fullscreen,
+            jvm_profile: self.jvm_profile.unwrap_or(JvmProfile::Modded),
+            extract_excludes: self.extract_excludes.unwrap_or_default(),
+            extra_tokens: self.extra_tokens.unwrap_or_default(),
+            extra_classpath: self.extra_classpath.unwrap_or_default(),
+            extra_jvm_args: self.extra_jvm_args.unwrap_or_default(),
+            system_properties: self.system_properties.unwrap_or_default(),
+            features,
+            user_type: self.user_type,
+            version_type_override: self.version_type_override,
+            server: self.server,
+            natives_dir: self.natives_dir,
+            auto_extract_natives: self.auto_extract_natives.unwrap_or(true),
+            log_file: self.log_file,
+            wrapper: self.wrapper,
+            clear_java_options: self.clear_java_options.unwrap_or(true),
+            offline: self.offline.unwrap_or(false),
+        })
     }
 }
 
 impl MinecraftLauncher {
+    pub fn manager(&self) -> &versions::VersionManager {
+        &self.manager
+    }
+
+    pub fn root_dir(&self) -> &path::Path {
+        self.game_root_dir.as_path()
+    }
+
+    pub fn game_dir(&self) -> &path::Path {
+        self.game_dir.as_path()
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
     pub fn generate_argument_map(&self,
                                  version: &versions::MinecraftVersion) -> HashMap<String, String> {
         let mut map: HashMap<String, String> = HashMap::new();
         let name = self.auth_info.user_profile().name();
         let uuid = self.auth_info.user_profile().uuid().simple();
-        let access_token = self.auth_info.access_token().simple();
+        let access_token = self.auth_info.access_token();
         map.insert("auth_access_token".to_owned(),
-                   format!("{}", access_token));
+                   access_token.to_string());
         map.insert("user_properties".to_owned(),
                    "{}".to_owned()); // TODO
         map.insert("user_property_map".to_owned(),
-                   "{}".to_owned()); // TODO
+                   serde_json::to_string(self.auth_info.user_profile().properties()).unwrap_or_else(|_| "[]".to_owned()));
+        // offline accounts get a sentinel so legacy versions do not try to validate the session
         map.insert("auth_session".to_owned(),
-                   format!("token:{}:{}", access_token, uuid));
+                   if self.auth_info.is_offline() {
+                       "-".to_owned()
+                   } else {
+                       format!("token:{}:{}", access_token, uuid)
+                   });
         map.insert("auth_player_name".to_owned(),
                    name.clone());
         map.insert("auth_uuid".to_owned(),
                    format!("{}", uuid));
+        if self.auth_info.account_kind() == yggdrasil::AccountKind::Microsoft {
+            if let Some(xuid) = self.auth_info.xuid() {
+                map.insert("auth_xuid".to_owned(), xuid.to_owned());
+            }
+            if let Some(client_id) = self.auth_info.client_id() {
+                map.insert("clientid".to_owned(), client_id.to_owned());
+            }
+        }
         map.insert("user_type".to_owned(),
-                   "legacy".to_owned());
+                   self.user_type.unwrap_or(self.auth_info.user_type()).as_str().to_owned());
         map.insert("profile_name".to_owned(),
                    name.clone());
         map.insert("version_name".to_owned(),
                    version.id().to_owned());
         map.insert("game_directory".to_owned(),
-                   self.game_root_dir.to_str().unwrap_or("").to_owned());
+                   self.game_dir.to_str().unwrap_or("").to_owned());
         map.insert("assets_root".to_owned(),
                    self.assets_dir.to_str().unwrap_or("").to_owned());
+        map.insert("game_assets".to_owned(),
+                   version.legacy_assets_dir(&self.manager, self.assets_dir.as_path(), self.game_dir.as_path())
+                       .as_ref().unwrap_or(&self.assets_dir).to_str().unwrap_or("").to_owned());
         map.insert("assets_index_name".to_owned(),
-                   version.asset_index(&self.manager).map(|i| i.id().to_owned()).unwrap_or_else(String::new));
+                   version.assets_index_id(&self.manager).unwrap_or_default());
         map.insert("version_type".to_owned(),
-                   version.version_type().to_owned());
+                   self.version_type_override.clone().unwrap_or_else(|| version.version_type().to_owned()));
         map.insert("resolution_width".to_owned(),
                    format!("{}", self.window_resolution.0));
         map.insert("resolution_height".to_owned(),
@@ -192,46 +642,99 @@ impl MinecraftLauncher {
         map.insert("launcher_version".to_owned(),
                    self.launcher_name_version.1.clone());
         map.insert("natives_directory".to_owned(),
-                   self.manager.get_natives_path(version.id()).to_str().unwrap_or("").to_owned());
+                   self.natives_path(version).and_then(|p| p.to_str().map(String::from)).unwrap_or_default());
         map.insert("primary_jar".to_owned(),
-                   version.version_jar_path(&self.manager).ok().and_then(|p| p.to_str().map(String::from)).unwrap_or_else(String::new));
+                   version.version_jar_path(&self.manager).ok().and_then(|p| p.to_str().map(String::from)).unwrap_or_default());
         map.insert("classpath".to_owned(),
-                   version.classpath(self.libraries_dir.as_path(), &self.manager).unwrap_or_else(|_| String::new()));
+                   self.classpath(version));
         map.insert("classpath_separator".to_owned(),
-                   ":".to_owned());
+                   versions::CLASSPATH_SEPARATOR.to_owned());
+        map.insert("library_directory".to_owned(),
+                   fs::canonicalize(self.libraries_dir.as_path()).as_ref().unwrap_or(&self.libraries_dir).to_str().unwrap_or("").to_owned());
+        map.extend(self.extra_tokens.iter().map(|(k, v)| (k.clone(), v.clone())));
         map
     }
 
+    fn natives_path(&self, version: &versions::MinecraftVersion) -> Option<path::PathBuf> {
+        match self.natives_dir {
+            Some(ref natives_dir) => Some(natives_dir.clone()),
+            None => self.manager.get_natives_path(version.id()).ok(),
+        }
+    }
+
+    fn classpath(&self, version: &versions::MinecraftVersion) -> String {
+        let mut entries = self.extra_classpath.clone();
+        entries.extend(version.classpath_entries(self.libraries_dir.as_path(), &self.manager).unwrap_or_else(|_| Vec::new()));
+        versions::join_classpath(&entries, versions::CLASSPATH_SEPARATOR).unwrap_or_else(|_| String::new())
+    }
+
+    // the srv lookup sends a dns query and blocks, so it is a separate step the caller runs before
+    // building arguments; a server left without a port is launched on the default one, and in offline
+    // mode the lookup is skipped so nothing the launcher does reaches the network
+    pub fn resolve_server(&mut self) {
+        if self.offline { return; }
+        if let Some(nameserver) = net::system_nameserver() { self.resolve_server_with(nameserver) }
+    }
+
+    pub fn resolve_server_with(&mut self, nameserver: SocketAddr) {
+        if self.offline { return; }
+        let resolved = match self.server {
+            Some((ref host, None)) => net::resolve_server_with(host, nameserver),
+            _ => return
+        };
+        self.server = Some((resolved.0, Some(resolved.1)));
+    }
+
+    pub fn server(&self) -> Option<(&str, Option<u16>)> {
+        self.server.as_ref().map(|&(ref host, port)| (host.as_str(), port))
+    }
+
+    // only reads what is already installed on disk, so offline launches never touch the network
     pub fn to_arguments(&self, version_id: &str) -> Result<LaunchArguments, versions::Error> {
+        self.to_arguments_for(&self.manager.version_of(version_id)?)
+    }
+
+    pub fn to_arguments_for(&self, minecraft_version: &versions::MinecraftVersion) -> Result<LaunchArguments, versions::Error> {
         let java_program_path = self.program_path.clone();
-        let minecraft_version = self.manager.version_of(version_id)?;
-        let java_main_class = minecraft_version.main_class(&self.manager).unwrap_or_else(String::new);
-        let game_natives = minecraft_version.to_native_collection(&self.manager, self.libraries_dir.as_path())?;
-        let mut jvm_options = vec![
-            JvmOption::new("-XX:+UseG1GC".to_owned()),
-            JvmOption::new("-XX:-UseAdaptiveSizePolicy".to_owned()),
-            JvmOption::new("-XX:-OmitStackTraceInFastThrow".to_owned()),
-            JvmOption::new("-Dfml.ignoreInvalidMinecraftCertificates=true".to_owned()),
-            JvmOption::new("-Dfml.ignorePatchDiscrepancies=true".to_owned()),
-        ];
+        let java_main_class = minecraft_version.main_class(&self.manager).unwrap_or_default();
+        let libraries_dir = self.libraries_dir.as_path();
+        let extract_excludes = self.extract_excludes.as_slice();
+        let game_natives = minecraft_version.to_native_collection_with_excludes(&self.manager, libraries_dir, extract_excludes)?;
+        let mut jvm_options = self.jvm_profile.options();
         let (min_mib, max_mib) = self.min_max_memory_mib;
         if min_mib > 0f32 { jvm_options.push(JvmOption::new(format!("-Xmn{}m", min_mib))) }
         if max_mib > 0f32 { jvm_options.push(JvmOption::new(format!("-Xmx{}m", max_mib))) }
+        jvm_options.extend(self.extra_jvm_args.iter().cloned().map(JvmOption::new));
         let mut game_options = Vec::new();
-        let map = self.generate_argument_map(&minecraft_version);
+        let map = self.generate_argument_map(minecraft_version);
         let game_native_path = path::PathBuf::from(map.get("natives_directory").unwrap());
         let strategy = parsing::ParameterStrategy::map(move |s| {
             let result = match map.get(&s) {
-                Some(ref string) => (*string).clone(),
+                Some(string) => string.clone(),
                 None => String::new()
             };
             result
         });
-        minecraft_version.collect_game_arguments(&self.manager, &mut game_options, &strategy)?;
-        minecraft_version.collect_jvm_arguments(&self.manager, &mut jvm_options, &strategy)?;
+        minecraft_version.collect_game_arguments(&self.manager, &mut game_options, &self.features, &strategy)?;
+        if self.auth_info.account_kind() != yggdrasil::AccountKind::Microsoft {
+            // an empty --xuid confuses some versions, so drop the microsoft-only options entirely
+            game_options.retain(|o| !MICROSOFT_ONLY_OPTIONS.contains(&o.name()) || o.value().is_some_and(|v| !v.is_empty()));
+        }
+        if self.fullscreen {
+            game_options.push(GameOption::new_single("--fullscreen".to_owned()));
+        }
+        if let Some((ref host, port)) = self.server {
+            game_options.push(GameOption::new_pair("--server".to_owned(), host.clone()));
+            game_options.push(GameOption::new_pair("--port".to_owned(), port.unwrap_or(net::DEFAULT_PORT).to_string()));
+        }
+        minecraft_version.collect_jvm_arguments(&self.manager, &mut jvm_options, &self.features, &self.system_properties, &strategy)?;
         Result::Ok(LaunchArguments {
             game_natives,
             game_native_path,
+            auto_extract_natives: self.auto_extract_natives,
+            log_file: self.log_file.clone(),
+            wrapper: self.wrapper.clone(),
+            clear_java_options: self.clear_java_options,
             game_options,
             jvm_options,
             java_main_class,
@@ -241,52 +744,193 @@ impl MinecraftLauncher {
 }
 
 impl LaunchArguments {
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(&LaunchSpec {
+            program: self.java_program_path.clone(),
+            main_class: self.java_main_class.clone(),
+            jvm_options: self.jvm_options.clone(),
+            game_options: self.game_options.clone(),
+            natives_directory: self.game_native_path.clone(),
+            natives: self.game_natives.jars(),
+            auto_extract_natives: self.auto_extract_natives,
+            log_file: self.log_file.clone(),
+            wrapper: self.wrapper.clone(),
+            clear_java_options: self.clear_java_options,
+        })
+    }
+
+    pub fn from_json(json: &str) -> Result<LaunchArguments, serde_json::Error> {
+        let spec: LaunchSpec = serde_json::from_str(json)?;
+        Result::Ok(LaunchArguments {
+            java_main_class: spec.main_class,
+            java_program_path: spec.program,
+            jvm_options: spec.jvm_options,
+            game_options: spec.game_options,
+            game_native_path: spec.natives_directory,
+            game_natives: versions::NativeCollection::from_jars(spec.natives),
+            auto_extract_natives: spec.auto_extract_natives,
+            log_file: spec.log_file,
+            wrapper: spec.wrapper,
+            clear_java_options: spec.clear_java_options,
+        })
+    }
+
     pub fn start(&self) -> Result<Child, versions::Error> {
-        self.extract_natives()?;
-        self.spawn_new_process()
+        if self.auto_extract_natives { self.extract_natives()?; }
+        match self.log_file {
+            Some((ref path, rotate)) => self.spawn_with_log_file(path.as_path(), rotate),
+            None => self.spawn_new_process()
+        }
+    }
+
+    fn spawn_with_log_file(&self, path: &path::Path, rotate: bool) -> Result<Child, versions::Error> {
+        if let Some(parent) = path.parent() { fs::create_dir_all(parent)? }
+        if rotate && path.is_file() {
+            let mut rotated = path.as_os_str().to_owned();
+            rotated.push(".1");
+            fs::rename(path, rotated)?;
+        }
+        let log = Arc::new(Mutex::new(fs::File::create(path)?));
+        let mut child = self.command()
+            .stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+        // the output still reaches the launcher's own stdout and stderr as it would without a log file
+        if let Some(stdout) = child.stdout.take() { tee_to_log(stdout, io::stdout(), log.clone()) }
+        if let Some(stderr) = child.stderr.take() { tee_to_log(stderr, io::stderr(), log) }
+        Result::Ok(child)
     }
 
     pub fn spawn_new_process(&self) -> Result<Child, versions::Error> {
-        Command::new(self.program()).args(self.args()).spawn().map_err(versions::Error::from)
+        self.command().spawn().map_err(versions::Error::from)
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(self.program());
+        command.args(self.args());
+        if self.clear_java_options {
+            for variable in JAVA_OPTIONS_VARIABLES { command.env_remove(variable); }
+        }
+        command
     }
 
     pub fn extract_natives(&self) -> Result<Vec<String>, versions::Error> {
         self.game_natives.extract_to(self.game_native_path.as_path())
     }
 
+    // with a wrapper the wrapper is the program and java becomes its first argument after the wrapper's own
     pub fn program(&self) -> String {
-        self.java_program_path.clone()
+        match self.wrapper {
+            Some((ref command, _)) => command.clone(),
+            None => self.java_program_path.clone()
+        }
     }
 
-    pub fn args(&self) -> Vec<String> {
+    pub fn java_program(&self) -> &str {
+        &self.java_program_path
+    }
+
+    pub fn main_class(&self) -> &str {
+        &self.java_main_class
+    }
+
+    pub fn jvm_options(&self) -> &[JvmOption] {
+        &self.jvm_options
+    }
+
+    pub fn game_options(&self) -> &[GameOption] {
+        &self.game_options
+    }
+
+    pub fn jvm_args(&self) -> Vec<String> {
+        self.jvm_options.iter().map(|option| option.0.clone()).collect()
+    }
+
+    pub fn game_args(&self) -> Vec<String> {
         let mut result = Vec::new();
-        for option in self.jvm_options.iter() {
-            match option {
-                &JvmOption(ref name) => {
-                    result.push(name.clone());
-                }
-            }
-        }
-        result.push(self.java_main_class.clone());
         for option in self.game_options.iter() {
-            match option {
-                &GameOption(ref name, Some(ref arg)) => {
+            match *option {
+                GameOption(ref name, Some(ref arg)) => {
                     result.push(name.clone());
                     result.push(arg.clone());
                 }
-                &GameOption(ref name, None) => {
+                GameOption(ref name, None) => {
                     result.push(name.clone());
                 }
             }
         }
         result
     }
+
+    pub fn args(&self) -> Vec<String> {
+        let mut result = Vec::new();
+        if let Some((_, ref args)) = self.wrapper {
+            result.extend(args.iter().cloned());
+            result.push(self.java_program_path.clone());
+        }
+        result.extend(self.jvm_args());
+        result.push(self.java_main_class.clone());
+        result.extend(self.game_args());
+        result
+    }
+}
+
+impl JvmProfile {
+    pub fn options(&self) -> Vec<JvmOption> {
+        let flags: &[&str] = match *self {
+            JvmProfile::Vanilla => &[
+                "-XX:+UnlockExperimentalVMOptions",
+                "-XX:+UseG1GC",
+                "-XX:G1NewSizePercent=20",
+                "-XX:G1ReservePercent=20",
+                "-XX:MaxGCPauseMillis=50",
+                "-XX:G1HeapRegionSize=32M",
+            ],
+            JvmProfile::Modded => &[
+                "-XX:+UseG1GC",
+                "-XX:-UseAdaptiveSizePolicy",
+                "-XX:-OmitStackTraceInFastThrow",
+                "-Dfml.ignoreInvalidMinecraftCertificates=true",
+                "-Dfml.ignorePatchDiscrepancies=true",
+            ],
+            JvmProfile::Aikar => &[
+                "-XX:+UseG1GC",
+                "-XX:+ParallelRefProcEnabled",
+                "-XX:MaxGCPauseMillis=200",
+                "-XX:+UnlockExperimentalVMOptions",
+                "-XX:+DisableExplicitGC",
+                "-XX:+AlwaysPreTouch",
+                "-XX:G1NewSizePercent=30",
+                "-XX:G1MaxNewSizePercent=40",
+                "-XX:G1HeapRegionSize=8M",
+                "-XX:G1ReservePercent=20",
+                "-XX:G1HeapWastePercent=5",
+                "-XX:G1MixedGCCountTarget=4",
+                "-XX:InitiatingHeapOccupancyPercent=15",
+                "-XX:G1MixedGCLiveThresholdPercent=90",
+                "-XX:G1RSetUpdatingPauseTimePercent=5",
+                "-XX:SurvivorRatio=32",
+                "-XX:+PerfDisableSharedMem",
+                "-XX:MaxTenuringThreshold=1",
+            ],
+            JvmProfile::Custom(ref flags) => return flags.iter().cloned().map(JvmOption::new).collect(),
+        };
+        flags.iter().map(|&flag| JvmOption::new(flag.to_owned())).collect()
+    }
 }
 
 impl JvmOption {
     pub fn new(arg: String) -> JvmOption {
         JvmOption(arg)
     }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for JvmOption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
 }
 
 impl GameOption {
@@ -297,4 +941,147 @@ impl GameOption {
     pub fn new_single(name: String) -> GameOption {
         GameOption(name, None)
     }
+
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+
+    pub fn value(&self) -> Option<&str> {
+        self.1.as_deref()
+    }
+}
+
+impl fmt::Display for GameOption {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GameOption(ref name, Some(ref arg)) => write!(f, "{} {}", name, arg),
+            GameOption(ref name, None) => f.write_str(name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_resolution() {
+        assert_eq!(parse_options_resolution("version:1343\noverrideWidth:1280\r\noverrideHeight:720\n"), Some((1280, 720)));
+        assert_eq!(parse_options_resolution("overrideWidth:0\noverrideHeight:0\n"), None);
+        assert_eq!(parse_options_resolution("overrideWidth:1280\nlastServer:\n"), None);
+        assert_eq!(parse_options_resolution("overrideWidth:wide\noverrideHeight:720"), None);
+    }
+
+    #[test]
+    fn jre_for_major() {
+        let candidates = vec![(path::PathBuf::from("/jre/8/bin/java"), 8), (path::PathBuf::from("/jre/21/bin/java"), 21),
+                              (path::PathBuf::from("/jre/17/bin/java"), 17)];
+        assert_eq!(select_jre_for_major(candidates.clone(), 17, false), Some("/jre/17/bin/java".to_owned()));
+        assert_eq!(select_jre_for_major(candidates.clone(), 16, false), None);
+        assert_eq!(select_jre_for_major(candidates.clone(), 16, true), Some("/jre/17/bin/java".to_owned()));
+        assert_eq!(select_jre_for_major(candidates, 22, true), None);
+        let profile = yggdrasil::Profile::new(yggdrasil::offline_uuid("Steve"), "Steve".to_owned(), Vec::new());
+        let result = builder().root_dir(path::Path::new("/nonexistent/.minecraft"))
+            .auth(yggdrasil::AuthInfo::new("0".to_owned(), profile, yggdrasil::AccountKind::Offline))
+            .jre_for_major(999).try_build();
+        match result {
+            Result::Err(BuildError::NoJreForMajor(major)) => assert_eq!(major, 999),
+            Result::Err(e) => panic!("unexpected error: {}", e),
+            Result::Ok(_) => panic!("a jre of major version 999 should not exist"),
+        }
+    }
+
+    fn offline_builder() -> MinecraftLauncherBuilder {
+        let profile = yggdrasil::Profile::new(yggdrasil::offline_uuid("Steve"), "Steve".to_owned(), Vec::new());
+        builder().root_dir(path::Path::new("/nonexistent/.minecraft")).jre(path::Path::new("/usr/bin/java"))
+            .auth(yggdrasil::AuthInfo::new("0".to_owned(), profile, yggdrasil::AccountKind::Offline))
+    }
+
+    // answers the first query with an srv record pointing at mc.example.com:25600
+    fn serve_srv() -> (::std::net::UdpSocket, thread::JoinHandle<()>) {
+        let socket = ::std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server = socket.try_clone().unwrap();
+        let handle = thread::spawn(move || {
+            let mut message = [0u8; 512];
+            let (size, peer) = server.recv_from(&mut message).unwrap();
+            let mut message = message[..size].to_vec();
+            message[2] = 0x81;
+            message[3] = 0x80;
+            message[7] = 1;
+            message.extend_from_slice(&[0xc0, 12, 0, 33, 0, 1, 0, 0, 0, 60, 0, 22, 0, 5, 0, 10, 0x64, 0x00]);
+            message.extend_from_slice(&[2, b'm', b'c', 7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 3, b'c', b'o', b'm', 0]);
+            server.send_to(&message, peer).unwrap();
+        });
+        (socket, handle)
+    }
+
+    #[test]
+    fn server_resolved_on_request() {
+        let json = r#"{ "id": "1.12.2", "type": "release", "time": "", "releaseTime": "",
+                        "mainClass": "net.minecraft.client.main.Main", "minecraftArguments": "" }"#;
+        let version: versions::MinecraftVersion = serde_json::from_str(json).unwrap();
+        let server_args = |launcher: &MinecraftLauncher| {
+            let args = launcher.to_arguments_for(&version).unwrap().game_args();
+            args[args.len() - 4..].to_vec()
+        };
+        let mut launcher = offline_builder().server("example.com", None).build();
+        assert_eq!(server_args(&launcher), vec!["--server", "example.com", "--port", "25565"]);
+        let (socket, server) = serve_srv();
+        launcher.resolve_server_with(socket.local_addr().unwrap());
+        server.join().unwrap();
+        assert_eq!(launcher.server(), Some(("mc.example.com", Some(25600))));
+        assert_eq!(server_args(&launcher), vec!["--server", "mc.example.com", "--port", "25600"]);
+    }
+
+    #[test]
+    fn offline_skips_resolution() {
+        let json = r#"{ "id": "1.12.2", "type": "release", "time": "", "releaseTime": "",
+                        "mainClass": "net.minecraft.client.main.Main", "minecraftArguments": "" }"#;
+        let version: versions::MinecraftVersion = serde_json::from_str(json).unwrap();
+        let nameserver = ::std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        nameserver.set_read_timeout(Some(::std::time::Duration::from_millis(200))).unwrap();
+        let mut launcher = offline_builder().offline(true).server("example.com", None).build();
+        assert!(launcher.is_offline());
+        launcher.resolve_server_with(nameserver.local_addr().unwrap());
+        launcher.resolve_server();
+        let args = launcher.to_arguments_for(&version).unwrap().game_args();
+        assert_eq!(args[args.len() - 4..].to_vec(), vec!["--server", "example.com", "--port", "25565"]);
+        assert_eq!(launcher.server(), Some(("example.com", None)));
+        // the nameserver never saw a query
+        assert!(nameserver.recv_from(&mut [0u8; 512]).is_err());
+    }
+
+    #[test]
+    fn wrapper_command() {
+        let arguments = LaunchArguments::from_json(r#"{
+            "program": "/usr/bin/java", "main_class": "net.minecraft.client.main.Main",
+            "jvm_options": ["-Xmx2048m"], "game_options": [["--demo", null]],
+            "natives_directory": "natives", "natives": [],
+            "wrapper": ["prime-run", ["--verbose"]]
+        }"#).unwrap();
+        assert_eq!(arguments.program(), "prime-run");
+        assert_eq!(arguments.java_program(), "/usr/bin/java");
+        assert_eq!(arguments.args(), vec!["--verbose", "/usr/bin/java", "-Xmx2048m", "net.minecraft.client.main.Main", "--demo"]);
+        assert!(arguments.to_json().unwrap().contains("prime-run"));
+        assert!(arguments.clear_java_options);
+    }
+
+    #[test]
+    fn java_options_removed() {
+        let json = r#"{
+            "program": "/usr/bin/java", "main_class": "net.minecraft.client.main.Main",
+            "jvm_options": [], "game_options": [], "natives_directory": "natives", "natives": []
+        }"#;
+        let removed = |arguments: LaunchArguments| -> Vec<String> {
+            let mut removed: Vec<String> = arguments.command().get_envs()
+                .filter(|&(_, value)| value.is_none())
+                .map(|(key, _)| key.to_string_lossy().into_owned()).collect();
+            removed.sort();
+            removed
+        };
+        assert_eq!(removed(LaunchArguments::from_json(json).unwrap()),
+                   vec!["JAVA_TOOL_OPTIONS", "JDK_JAVA_OPTIONS", "_JAVA_OPTIONS"]);
+        let json = json.replace("\"natives\": []", "\"natives\": [], \"clear_java_options\": false");
+        assert!(removed(LaunchArguments::from_json(&json).unwrap()).is_empty());
+    }
 }
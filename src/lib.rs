@@ -1,24 +1,38 @@
 extern crate futures;
 extern crate hyper;
+#[cfg(feature = "tls")]
 extern crate hyper_tls;
+#[cfg(windows)]
+extern crate ipconfig;
+#[macro_use]
+extern crate lazy_static;
+extern crate md5;
 extern crate serde;
 #[macro_use]
 extern crate serde_json;
 #[macro_use]
 extern crate serde_derive;
+extern crate sha1;
+extern crate sha2;
 extern crate tokio_core;
 extern crate uuid;
 extern crate zip;
 
+pub mod checksum;
 pub mod launcher;
+pub mod mirrors;
+pub mod net;
 pub mod parsing;
+pub mod progress;
 pub mod requests;
 pub mod versions;
 pub mod yggdrasil;
 
 #[cfg(test)]
 mod tests {
+    // needs a local 1.12.2 install and a jre, run it with --ignored
     #[test]
+    #[ignore]
     fn start_minecraft() {
         use std::env;
         use launcher;
@@ -0,0 +1,122 @@
+#![allow(dead_code)]
+
+const LAUNCHER_META_HOSTS: &[&str] = &["https://launchermeta.mojang.com/", "https://piston-meta.mojang.com/"];
+const LIBRARIES_HOSTS: &[&str] = &["https://libraries.minecraft.net/"];
+const RESOURCES_HOSTS: &[&str] = &["https://resources.download.minecraft.net/"];
+// asset indexes of versions too old to list a url are only hosted on the old s3 bucket
+const LEGACY_ASSETS_HOSTS: &[&str] = &["https://s3.amazonaws.com/Minecraft.Download/"];
+const AUTH_SERVER_HOSTS: &[&str] = &["https://authserver.mojang.com/"];
+const SESSION_SERVER_HOSTS: &[&str] = &["https://sessionserver.mojang.com/"];
+
+#[derive(Clone, Debug)]
+pub struct MirrorConfig {
+    launcher_meta: String,
+    libraries: String,
+    resources: String,
+    legacy_assets: String,
+    auth_server: String,
+    session_server: String,
+}
+
+impl Default for MirrorConfig {
+    fn default() -> Self {
+        MirrorConfig::official()
+    }
+}
+
+impl MirrorConfig {
+    pub fn official() -> MirrorConfig {
+        MirrorConfig {
+            launcher_meta: LAUNCHER_META_HOSTS[0].to_owned(),
+            libraries: LIBRARIES_HOSTS[0].to_owned(),
+            resources: RESOURCES_HOSTS[0].to_owned(),
+            legacy_assets: LEGACY_ASSETS_HOSTS[0].to_owned(),
+            auth_server: AUTH_SERVER_HOSTS[0].to_owned(),
+            session_server: SESSION_SERVER_HOSTS[0].to_owned(),
+        }
+    }
+
+    pub fn bmclapi() -> MirrorConfig {
+        MirrorConfig::official()
+            .launcher_meta("https://bmclapi2.bangbang93.com/")
+            .libraries("https://bmclapi2.bangbang93.com/maven/")
+            .resources("https://bmclapi2.bangbang93.com/assets/")
+    }
+
+    pub fn launcher_meta(mut self, base_url: &str) -> Self {
+        self.launcher_meta = MirrorConfig::normalize(base_url);
+        self
+    }
+
+    pub fn libraries(mut self, base_url: &str) -> Self {
+        self.libraries = MirrorConfig::normalize(base_url);
+        self
+    }
+
+    pub fn resources(mut self, base_url: &str) -> Self {
+        self.resources = MirrorConfig::normalize(base_url);
+        self
+    }
+
+    pub fn legacy_assets(mut self, base_url: &str) -> Self {
+        self.legacy_assets = MirrorConfig::normalize(base_url);
+        self
+    }
+
+    pub fn auth_server(mut self, base_url: &str) -> Self {
+        self.auth_server = MirrorConfig::normalize(base_url);
+        self
+    }
+
+    pub fn session_server(mut self, base_url: &str) -> Self {
+        self.session_server = MirrorConfig::normalize(base_url);
+        self
+    }
+
+    pub fn launcher_meta_url(&self, path: &str) -> String {
+        format!("{}{}", self.launcher_meta, path)
+    }
+
+    pub fn libraries_url(&self, path: &str) -> String {
+        format!("{}{}", self.libraries, path)
+    }
+
+    pub fn resources_url(&self, path: &str) -> String {
+        format!("{}{}", self.resources, path)
+    }
+
+    pub fn legacy_assets_url(&self, path: &str) -> String {
+        format!("{}{}", self.legacy_assets, path)
+    }
+
+    pub fn auth_server_url(&self, path: &str) -> String {
+        format!("{}{}", self.auth_server, path)
+    }
+
+    pub fn session_server_url(&self, path: &str) -> String {
+        format!("{}{}", self.session_server, path)
+    }
+
+    pub fn rewrite(&self, url: &str) -> String {
+        let candidates = [
+            (LAUNCHER_META_HOSTS, &self.launcher_meta),
+            (LIBRARIES_HOSTS, &self.libraries),
+            (RESOURCES_HOSTS, &self.resources),
+            (LEGACY_ASSETS_HOSTS, &self.legacy_assets),
+            (AUTH_SERVER_HOSTS, &self.auth_server),
+            (SESSION_SERVER_HOSTS, &self.session_server),
+        ];
+        for &(hosts, base_url) in candidates.iter() {
+            for host in hosts.iter() {
+                if let Some(rest) = url.strip_prefix(host) {
+                    return format!("{}{}", base_url, rest);
+                }
+            }
+        }
+        url.to_owned()
+    }
+
+    fn normalize(base_url: &str) -> String {
+        if base_url.ends_with('/') { base_url.to_owned() } else { format!("{}/", base_url) }
+    }
+}
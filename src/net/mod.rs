@@ -0,0 +1,148 @@
+#![allow(dead_code)]
+
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::net::{SocketAddr, UdpSocket};
+
+pub const DEFAULT_PORT: u16 = 25565;
+
+const DNS_TIMEOUT_SECS: u64 = 2;
+const DNS_TYPE_SRV: u16 = 33;
+const DNS_CLASS_IN: u16 = 1;
+
+pub fn resolve_server(host: &str) -> (String, u16) {
+    match system_nameserver() {
+        Some(nameserver) => resolve_server_with(host, nameserver),
+        None => (host.to_owned(), DEFAULT_PORT)
+    }
+}
+
+pub fn resolve_server_with(host: &str, nameserver: SocketAddr) -> (String, u16) {
+    match resolve_srv_with(host, nameserver) {
+        Result::Ok(Some(result)) => result,
+        _ => (host.to_owned(), DEFAULT_PORT)
+    }
+}
+
+pub fn resolve_srv_with(host: &str, nameserver: SocketAddr) -> io::Result<Option<(String, u16)>> {
+    let id = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos() as u16).unwrap_or(0);
+    let query = build_srv_query(id, &format!("_minecraft._tcp.{}", host))?;
+    let bind_addr = if nameserver.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let socket = UdpSocket::bind(bind_addr)?;
+    socket.set_read_timeout(Some(Duration::from_secs(DNS_TIMEOUT_SECS)))?;
+    socket.send_to(&query, nameserver)?;
+    let mut buffer = [0u8; 512];
+    let (size, _) = socket.recv_from(&mut buffer)?;
+    Result::Ok(parse_srv_response(id, &buffer[..size]))
+}
+
+#[cfg(unix)]
+pub fn system_nameserver() -> Option<SocketAddr> {
+    use std::fs;
+    use std::net::IpAddr;
+    let resolv_conf = fs::read_to_string("/etc/resolv.conf").ok()?;
+    resolv_conf.lines().filter_map(|line| {
+        let mut parts = line.split_whitespace();
+        match (parts.next(), parts.next()) {
+            (Some("nameserver"), Some(address)) => address.parse::<IpAddr>().ok(),
+            _ => None
+        }
+    }).next().map(|ip| SocketAddr::new(ip, 53))
+}
+
+#[cfg(windows)]
+pub fn system_nameserver() -> Option<SocketAddr> {
+    use ipconfig::{self, OperStatus};
+    let adapters = ipconfig::get_adapters().ok()?;
+    adapters.iter().filter(|adapter| adapter.oper_status() == OperStatus::IfOperStatusUp)
+        .flat_map(|adapter| adapter.dns_servers().iter().cloned())
+        .next().map(|ip| SocketAddr::new(ip, 53))
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn system_nameserver() -> Option<SocketAddr> {
+    None
+}
+
+fn build_srv_query(id: u16, name: &str) -> io::Result<Vec<u8>> {
+    let mut query = Vec::with_capacity(512);
+    query.extend_from_slice(&[(id >> 8) as u8, id as u8, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Result::Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid host name"));
+        }
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&[(DNS_TYPE_SRV >> 8) as u8, DNS_TYPE_SRV as u8, 0, DNS_CLASS_IN as u8]);
+    Result::Ok(query)
+}
+
+fn read_u16(message: &[u8], offset: usize) -> Option<u16> {
+    Some(((*message.get(offset)? as u16) << 8) | *message.get(offset + 1)? as u16)
+}
+
+fn read_name(message: &[u8], offset: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let (mut position, mut end, mut jumps) = (offset, None, 0);
+    loop {
+        let length = *message.get(position)? as usize;
+        if length == 0 {
+            return Some((labels.join("."), end.unwrap_or(position + 1)));
+        } else if length & 0xc0 == 0xc0 {
+            jumps += 1;
+            if jumps > 16 { return None; }
+            if end.is_none() { end = Some(position + 2); }
+            position = read_u16(message, position)? as usize & 0x3fff;
+        } else {
+            let label = message.get(position + 1..position + 1 + length)?;
+            labels.push(String::from_utf8_lossy(label).into_owned());
+            position += 1 + length;
+        }
+    }
+}
+
+fn parse_srv_response(id: u16, message: &[u8]) -> Option<(String, u16)> {
+    if read_u16(message, 0)? != id || read_u16(message, 2)? & 0x000f != 0 { return None; }
+    let (questions, answers) = (read_u16(message, 4)?, read_u16(message, 6)?);
+    let mut position = 12;
+    for _ in 0..questions {
+        position = read_name(message, position)?.1 + 4;
+    }
+    let mut best: Option<(u16, u16, String, u16)> = None;
+    for _ in 0..answers {
+        position = read_name(message, position)?.1;
+        let record_type = read_u16(message, position)?;
+        let data_length = read_u16(message, position + 8)? as usize;
+        let data = position + 10;
+        if record_type == DNS_TYPE_SRV {
+            let (priority, weight, port) = (read_u16(message, data)?, read_u16(message, data + 2)?, read_u16(message, data + 4)?);
+            let target = read_name(message, data + 6)?.0;
+            // lowest priority wins, then highest weight
+            if best.as_ref().is_none_or(|&(p, w, _, _)| priority < p || (priority == p && weight > w)) {
+                best = Some((priority, weight, target, port));
+            }
+        }
+        position = data + data_length;
+    }
+    best.map(|(_, _, target, port)| (target, port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_srv_answer() {
+        let mut message = build_srv_query(0x1234, "_minecraft._tcp.example.com").unwrap();
+        message[2] = 0x81;
+        message[3] = 0x80;
+        message[7] = 1;
+        // answer: name pointer to the question, SRV, IN, ttl, rdata
+        message.extend_from_slice(&[0xc0, 12, 0, 33, 0, 1, 0, 0, 0, 60, 0, 22, 0, 5, 0, 10, 0x63, 0xdd]);
+        message.extend_from_slice(&[2, b'm', b'c', 7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 3, b'c', b'o', b'm', 0]);
+        assert_eq!(parse_srv_response(0x1234, &message), Some(("mc.example.com".to_owned(), 25565)));
+        assert_eq!(parse_srv_response(0x4321, &message), None);
+    }
+}
@@ -1,17 +1,37 @@
 #![allow(dead_code)]
 
 use std::rc::Rc;
+use std::fmt;
+use std::error;
+use std::iter::Peekable;
+use std::str::CharIndices;
 
 #[derive(Clone)]
 pub enum ParameterStrategy {
     Ignore,
-    Map(Rc<Fn(String) -> String>),
+    Map(Rc<dyn Fn(String) -> String>),
 }
 
 pub struct ArgumentIterator<'a> {
     strategy: &'a ParameterStrategy,
-    chars: Vec<char>,
-    index: usize,
+    chars: Peekable<CharIndices<'a>>,
+    literal_backslashes: bool,
+    failed: bool,
+}
+
+pub struct TokenIterator<'a>(ArgumentIterator<'a>);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    UnterminatedQuote,
+    UnterminatedParameter,
+    DanglingEscape,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    kind: ParseErrorKind,
+    offset: usize,
 }
 
 impl ParameterStrategy {
@@ -24,171 +44,634 @@ impl ParameterStrategy {
     }
 }
 
+impl ParseError {
+    pub fn kind(&self) -> ParseErrorKind {
+        self.kind
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ParseErrorKind::UnterminatedQuote => write!(f, "unterminated quote at byte {}", self.offset),
+            ParseErrorKind::UnterminatedParameter => write!(f, "unterminated parameter at byte {}", self.offset),
+            ParseErrorKind::DanglingEscape => write!(f, "dangling escape at byte {}", self.offset),
+        }
+    }
+}
+
+impl error::Error for ParseError {}
+
 impl<'a> Iterator for ArgumentIterator<'a> {
     type Item = String;
 
     fn next(&mut self) -> Option<String> {
-        let (index, result) = parse_whole_string(&self.chars, self.index, &self.strategy);
-        self.index = index;
+        self.next_token().map(|(token, _)| token)
+    }
+}
+
+impl<'a> Iterator for TokenIterator<'a> {
+    type Item = (String, bool);
+
+    fn next(&mut self) -> Option<(String, bool)> {
+        self.0.next_token()
+    }
+}
+
+impl<'a> ArgumentIterator<'a> {
+    pub fn literal_backslashes(mut self, literal: bool) -> Self {
+        self.literal_backslashes = literal;
+        self
+    }
+
+    fn next_token(&mut self) -> Option<(String, bool)> {
+        if self.failed { return None; }
+        let result = parse_whole_string(&mut self.chars, self.strategy, self.literal_backslashes).ok();
+        self.failed = result.is_none();
         result
     }
 }
 
-pub fn parse<'a>(string: &str, strategy: &'a ParameterStrategy) -> ArgumentIterator<'a> {
-    ArgumentIterator { strategy, chars: string.chars().collect(), index: 0 }
+impl<'a> TokenIterator<'a> {
+    pub fn literal_backslashes(self, literal: bool) -> Self {
+        TokenIterator(self.0.literal_backslashes(literal))
+    }
 }
 
-fn parse_whole_string(chars: &Vec<char>, original_pos: usize, strategy: &ParameterStrategy) -> (usize, Option<String>) {
-    let mut index = original_pos;
-    let mut result: String = String::new();
-    while let Some(c) = chars.get(index) { if c.is_whitespace() { index += 1; } else { break; } }
+pub fn parse<'a>(string: &'a str, strategy: &'a ParameterStrategy) -> ArgumentIterator<'a> {
+    let literal_backslashes = cfg!(target_os = "windows");
+    ArgumentIterator { strategy, chars: string.char_indices().peekable(), literal_backslashes, failed: false }
+}
+
+pub fn parse_checked(string: &str, strategy: &ParameterStrategy) -> Result<Vec<String>, ParseError> {
+    let literal_backslashes = cfg!(target_os = "windows");
+    let mut chars = string.char_indices().peekable();
+    let mut result = Vec::new();
+    loop {
+        while let Some(c) = peek_char(&mut chars) { if c.is_whitespace() { chars.next(); } else { break; } }
+        if chars.peek().is_none() { return Result::Ok(result); }
+        result.push(parse_whole_string(&mut chars, strategy, literal_backslashes)?.0);
+    }
+}
+
+// splits a user supplied command line the way CommandLineToArgvW does, for strings pasted on windows
+// where backslashes in paths are literal; carets belong to cmd.exe and are kept as they are
+pub fn split_windows(string: &str) -> Vec<String> {
+    let mut chars = string.chars().peekable();
+    let mut result = Vec::new();
     loop {
-        match chars.get(index) {
-            None => return (index, Some(result)),
-            Some(c) if c.is_whitespace() => return (index, Some(result)),
-            Some(&'$') => match parse_dollar_parameters(chars, index, strategy) {
-                (i, None) => return (i, None),
-                (i, Some(string)) => {
-                    result.push_str(&string);
-                    index = i;
+        while let Some(&c) = chars.peek() { if c == ' ' || c == '\t' { chars.next(); } else { break; } }
+        if chars.peek().is_none() { return result; }
+        let (mut argument, mut quoted) = (String::new(), false);
+        loop {
+            match chars.next() {
+                None => break,
+                Some(' ') | Some('\t') if !quoted => break,
+                Some('\\') => {
+                    let mut backslashes = 1;
+                    while chars.peek() == Some(&'\\') {
+                        chars.next();
+                        backslashes += 1;
+                    }
+                    if chars.peek() == Some(&'"') {
+                        // only backslashes directly before a quote escape, in pairs
+                        argument.extend((0..backslashes / 2).map(|_| '\\'));
+                        if backslashes % 2 == 1 {
+                            chars.next();
+                            argument.push('"');
+                        }
+                    } else {
+                        argument.extend((0..backslashes).map(|_| '\\'));
+                    }
                 }
+                Some('"') => if quoted && chars.peek() == Some(&'"') {
+                    chars.next();
+                    argument.push('"');
+                } else {
+                    quoted = !quoted;
+                }
+                Some(c) => argument.push(c)
             }
-            Some(&'\'') => match parse_single_quote(chars, index, strategy) {
-                (i, None) => return (i, None),
-                (i, Some(string)) => {
-                    result.push_str(&string);
-                    index = i;
+        }
+        result.push(argument);
+    }
+}
+
+pub fn tokenize<'a>(string: &'a str, strategy: &'a ParameterStrategy) -> TokenIterator<'a> {
+    TokenIterator(parse(string, strategy))
+}
+
+pub fn substitute(string: &str, strategy: &ParameterStrategy) -> String {
+    let mut chars = string.char_indices().peekable();
+    let mut result = String::new();
+    while let Some((index, c)) = chars.peek().cloned() {
+        if c == '$' {
+            match parse_dollar_parameters(&mut chars, strategy) {
+                Result::Ok(parameter) => result.push_str(&parameter),
+                Result::Err(_) => {
+                    result.push_str(&string[index..]);
+                    break;
                 }
             }
-            Some(&'\"') => match parse_double_quote(chars, index, strategy) {
-                (i, None) => return (i, None),
-                (i, Some(string)) => {
-                    result.push_str(&string);
-                    index = i;
+        } else {
+            result.push(c);
+            chars.next();
+        }
+    }
+    result
+}
+
+fn error_at(kind: ParseErrorKind, offset: usize) -> ParseError {
+    ParseError { kind, offset }
+}
+
+fn peek_offset(chars: &mut Peekable<CharIndices>) -> usize {
+    chars.peek().map(|&(i, _)| i).unwrap_or(0)
+}
+
+fn peek_char(chars: &mut Peekable<CharIndices>) -> Option<char> {
+    chars.peek().map(|&(_, c)| c)
+}
+
+fn next_char(chars: &mut Peekable<CharIndices>) -> Option<char> {
+    chars.next().map(|(_, c)| c)
+}
+
+fn parse_whole_string(chars: &mut Peekable<CharIndices>,
+                      strategy: &ParameterStrategy,
+                      literal_backslashes: bool) -> Result<(String, bool), ParseError> {
+    let mut result: String = String::new();
+    let mut quoted = false;
+    while let Some(c) = peek_char(chars) { if c.is_whitespace() { chars.next(); } else { break; } }
+    loop {
+        match peek_char(chars) {
+            None => return Result::Ok((result, quoted)),
+            Some(c) if c.is_whitespace() => return Result::Ok((result, quoted)),
+            Some('$') => result.push_str(&parse_dollar_parameters(chars, strategy)?),
+            Some('\'') => {
+                quoted = true;
+                result.push_str(&parse_single_quote(chars, strategy)?)
+            }
+            Some('\"') => {
+                quoted = true;
+                result.push_str(&parse_double_quote(chars, strategy, literal_backslashes)?)
+            }
+            Some('\\') if literal_backslashes => {
+                chars.next();
+                if let Some('\"') = peek_char(chars) {
+                    if let &ParameterStrategy::Ignore = strategy { result.push('\\') }
+                    chars.next();
+                    result.push('\"');
+                } else {
+                    result.push('\\');
                 }
             }
-            Some(&'\\') => {
-                index += 1;
-                match chars.get(index) {
-                    Some(c @ &'\n') | Some(c @ &'\r') => {
+            Some('\\') => {
+                let offset = peek_offset(chars);
+                chars.next();
+                match next_char(chars) {
+                    Some(c @ '\n') | Some(c @ '\r') => {
                         if let &ParameterStrategy::Ignore = strategy {
                             result.push('\\');
-                            result.push(c.clone());
+                            result.push(c);
                         }
-                        index += 1;
+                        if c == '\r' { skip_line_feed(chars, strategy, &mut result) }
                     }
                     Some(c) => {
                         if let &ParameterStrategy::Ignore = strategy { result.push('\\') }
-                        index += 1;
-                        result.push(c.clone());
+                        result.push(c);
                     }
-                    None => return (original_pos, None)
+                    None => return Result::Err(error_at(ParseErrorKind::DanglingEscape, offset))
                 }
             }
             Some(c) => {
-                index += 1;
-                result.push(c.clone());
+                chars.next();
+                result.push(c);
             }
         }
     }
 }
 
-fn parse_single_quote(chars: &Vec<char>, pos: usize, strategy: &ParameterStrategy) -> (usize, Option<String>) {
-    let mut index = pos;
+fn parse_single_quote(chars: &mut Peekable<CharIndices>, strategy: &ParameterStrategy) -> Result<String, ParseError> {
     let mut result: String = String::new();
     if let &ParameterStrategy::Ignore = strategy { result.push('\'') }
-    index += 1;
+    let offset = peek_offset(chars);
+    chars.next();
     loop {
-        if let Some(c) = chars.get(index) {
-            if c == &'\'' {
+        match next_char(chars) {
+            Some('\'') => {
                 if let &ParameterStrategy::Ignore = strategy { result.push('\'') }
-                index += 1;
-                return (index, Some(result));
+                return Result::Ok(result);
             }
-            result.push(c.clone());
-            index += 1;
-        } else {
-            return (pos, None);
+            Some(c) => result.push(c),
+            None => return Result::Err(error_at(ParseErrorKind::UnterminatedQuote, offset))
         }
     }
 }
 
-fn parse_double_quote(chars: &Vec<char>, pos: usize, strategy: &ParameterStrategy) -> (usize, Option<String>) {
-    let mut index = pos;
+fn parse_double_quote(chars: &mut Peekable<CharIndices>,
+                      strategy: &ParameterStrategy,
+                      literal_backslashes: bool) -> Result<String, ParseError> {
     let mut result: String = String::new();
     if let &ParameterStrategy::Ignore = strategy { result.push('\"') }
-    index += 1;
+    let offset = peek_offset(chars);
+    chars.next();
     loop {
-        match chars.get(index) {
-            Some(&'\\') => {
-                index += 1;
-                match chars.get(index) {
-                    Some(c @ &'\"') | Some(c @ &'$') => {
+        match peek_char(chars) {
+            Some('\\') => {
+                chars.next();
+                match peek_char(chars) {
+                    Some(c @ '\"') | Some(c @ '$') if !literal_backslashes || c == '\"' => {
                         if let &ParameterStrategy::Ignore = strategy { result.push('\\') }
-                        index += 1;
-                        result.push(c.clone());
+                        chars.next();
+                        result.push(c);
                     }
-                    Some(c @ &'\n') | Some(c @ &'\r') => {
+                    Some(c @ '\n') | Some(c @ '\r') => {
                         if let &ParameterStrategy::Ignore = strategy {
                             result.push('\\');
-                            result.push(c.clone());
+                            result.push(c);
                         }
-                        index += 1;
+                        chars.next();
+                        if c == '\r' { skip_line_feed(chars, strategy, &mut result) }
                     }
-                    Some(&_) => result.push('\\'),
-                    None => return (pos, None)
+                    Some(_) => result.push('\\'),
+                    None => return Result::Err(error_at(ParseErrorKind::UnterminatedQuote, offset))
                 }
             }
-            Some(&'\"') => {
+            Some('\"') => {
                 if let &ParameterStrategy::Ignore = strategy { result.push('\"') }
-                index += 1;
-                return (index, Some(result));
-            }
-            Some(&'$') => match parse_dollar_parameters(chars, index, strategy) {
-                (i, None) => return (i, None),
-                (i, Some(string)) => {
-                    result.push_str(&string);
-                    index = i;
-                }
+                chars.next();
+                return Result::Ok(result);
             }
+            Some('$') => result.push_str(&parse_dollar_parameters(chars, strategy)?),
             Some(c) => {
-                result.push(c.clone());
-                index += 1;
+                result.push(c);
+                chars.next();
             }
-            None => return (pos, None)
+            None => return Result::Err(error_at(ParseErrorKind::UnterminatedQuote, offset))
         }
     }
 }
 
-fn parse_dollar_parameters(chars: &Vec<char>, pos: usize, strategy: &ParameterStrategy) -> (usize, Option<String>) {
-    match strategy {
-        &ParameterStrategy::Ignore => return (pos + 1, Some("$".to_owned())),
-        &ParameterStrategy::Map(ref b) => {
-            let mut index = pos + 1;
+fn parse_dollar_parameters(chars: &mut Peekable<CharIndices>, strategy: &ParameterStrategy) -> Result<String, ParseError> {
+    let offset = peek_offset(chars);
+    chars.next();
+    match *strategy {
+        ParameterStrategy::Ignore => Result::Ok("$".to_owned()),
+        ParameterStrategy::Map(ref b) => {
             let mut result = String::new();
+            loop {
+                match peek_char(chars) {
+                    Some('{') => {
+                        chars.next();
+                        loop {
+                            match next_char(chars) {
+                                Some('}') => return Result::Ok(map_braced_parameter(b.as_ref(), result)),
+                                Some(c) => result.push(c),
+                                None => return Result::Err(error_at(ParseErrorKind::UnterminatedParameter, offset))
+                            }
+                        }
+                    }
+                    Some(c @ 'a'..='z') | Some(c @ 'A'..='Z') |
+                    Some(c @ '0'..='9') | Some(c @ '_') => {
+                        result.push(c);
+                        chars.next();
+                    }
+                    _ if result.is_empty() => return Result::Ok("$".to_owned()),
+                    _ => return Result::Ok(b.as_ref()(result))
+                }
+            }
+        }
+    }
+}
+
+fn skip_line_feed(chars: &mut Peekable<CharIndices>, strategy: &ParameterStrategy, result: &mut String) {
+    if let Some('\n') = peek_char(chars) {
+        if let &ParameterStrategy::Ignore = strategy { result.push('\n') }
+        chars.next();
+    }
+}
+
+fn map_braced_parameter(function: &dyn Fn(String) -> String, parameter: String) -> String {
+    match parameter.find(":-") {
+        Some(i) => {
+            let mapped = function(parameter[..i].to_owned());
+            if mapped.is_empty() { parameter[i + 2..].to_owned() } else { mapped }
+        }
+        None => function(parameter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(string: &str, strategy: &ParameterStrategy) -> Vec<String> {
+        parse(string, strategy).literal_backslashes(false).take_while(|s| !s.is_empty()).collect()
+    }
+
+    fn strategy() -> ParameterStrategy {
+        ParameterStrategy::map(|s| if s == "missing" { String::new() } else { format!("<{}>", s) })
+    }
+
+    #[test]
+    fn parse_parameters() {
+        let string = "--username ${auth_player_name} --version ${version_name}";
+        assert_eq!(tokens(string, &ParameterStrategy::ignore()),
+                   vec!["--username", "${auth_player_name}", "--version", "${version_name}"]);
+        assert_eq!(tokens(string, &strategy()),
+                   vec!["--username", "<auth_player_name>", "--version", "<version_name>"]);
+        assert_eq!(tokens("$ ${missing:-fallback} $abc_1-rest", &strategy()),
+                   vec!["$", "fallback", "<abc_1>-rest"]);
+    }
+
+    #[test]
+    fn parse_quotes_and_escapes() {
+        let string = "'single quoted' \"double $name\" \\\"escaped\\\"";
+        assert_eq!(tokens(string, &ParameterStrategy::ignore()),
+                   vec!["'single quoted'", "\"double $name\"", "\\\"escaped\\\""]);
+        assert_eq!(tokens(string, &strategy()),
+                   vec!["single quoted", "double <name>", "\"escaped\""]);
+    }
+
+    #[test]
+    fn parse_unterminated() {
+        assert_eq!(parse("\"unterminated", &strategy()).next(), None);
+        assert_eq!(parse("${unterminated", &strategy()).next(), None);
+        assert_eq!(parse("trailing\\", &ParameterStrategy::ignore()).literal_backslashes(false).next(), None);
+    }
+
+    #[test]
+    fn tokenize_quoted() {
+        let tokens: Vec<(String, bool)> = tokenize("plain 'single quoted' pre\"fix\" \\\"escaped\\\"", &strategy())
+            .literal_backslashes(false).take_while(|(s, _)| !s.is_empty()).collect();
+        assert_eq!(tokens, vec![("plain".to_owned(), false), ("single quoted".to_owned(), true),
+                                ("prefix".to_owned(), true), ("\"escaped\"".to_owned(), false)]);
+    }
+
+    #[test]
+    fn parse_checked_errors() {
+        assert_eq!(parse_checked("--a  b  ''", &strategy()), Result::Ok(vec!["--a".to_owned(), "b".to_owned(), String::new()]));
+        let error = parse_checked("--a \"unterminated", &strategy()).unwrap_err();
+        assert_eq!((error.kind(), error.offset()), (ParseErrorKind::UnterminatedQuote, 4));
+        let error = parse_checked("--a 'b' ${unterminated", &strategy()).unwrap_err();
+        assert_eq!((error.kind(), error.offset()), (ParseErrorKind::UnterminatedParameter, 8));
+        if !cfg!(target_os = "windows") {
+            let error = parse_checked("--a trailing\\", &strategy()).unwrap_err();
+            assert_eq!((error.kind(), error.offset()), (ParseErrorKind::DanglingEscape, 12));
+        }
+    }
+
+    #[test]
+    fn split_windows_arguments() {
+        assert_eq!(split_windows(r#"-Djava.home="C:\Program Files\Java" -Xmx2G"#),
+                   vec![r#"-Djava.home=C:\Program Files\Java"#, "-Xmx2G"]);
+        assert_eq!(split_windows(r#""C:\Program Files\Java\" -a"#), vec![r#"C:\Program Files\Java" -a"#]);
+        assert_eq!(split_windows(r#""C:\Program Files\Java\\" -a"#), vec![r#"C:\Program Files\Java\"#, "-a"]);
+        assert_eq!(split_windows("a\\\\b \"\" \"say \"\"hi\"\"\" ^x\ty"), vec![r#"a\\b"#, "", r#"say "hi""#, "^x", "y"]);
+        assert_eq!(split_windows("  "), Vec::<String>::new());
+    }
+
+    #[test]
+    fn substitute_parameters() {
+        assert_eq!(substitute("-Dos.name=Windows 10 ${name} 'quoted'", &strategy()),
+                   "-Dos.name=Windows 10 <name> 'quoted'");
+        assert_eq!(substitute("${missing:-fallback}-${unterminated", &strategy()),
+                   "fallback-${unterminated");
+    }
+
+    #[test]
+    fn parse_windows_paths() {
+        let strategy = ParameterStrategy::map(|s| match s.as_str() {
+            "game_directory" => "C:\\Users\\Steve\\AppData\\Roaming\\.minecraft".to_owned(),
+            _ => String::new()
+        });
+        let string = "--gameDir ${game_directory} \"${game_directory}\"";
+        assert_eq!(tokens(string, &strategy),
+                   vec!["--gameDir", "C:\\Users\\Steve\\AppData\\Roaming\\.minecraft",
+                        "C:\\Users\\Steve\\AppData\\Roaming\\.minecraft"]);
+        let string = "-Djava.home=C:\\Java\\bin \"C:\\Program Files\\\"quoted\\\"\"\r\n--next";
+        assert_eq!(parse(string, &strategy).literal_backslashes(true).take_while(|s| !s.is_empty()).collect::<Vec<_>>(),
+                   vec!["-Djava.home=C:\\Java\\bin", "C:\\Program Files\"quoted\"", "--next"]);
+        assert_eq!(parse("line\\\r\ncontinued", &strategy).literal_backslashes(false).next(),
+                   Some("linecontinued".to_owned()));
+    }
+
+    // the Vec<char> tokenizer as it stood before the move to CharIndices: the original code with only the
+    // `${key:-fallback}` defaults (map_braced_parameter) added, copied as is apart from `..=` ranges and `dyn`
+    #[allow(clippy::all)]
+    mod previous {
+        use super::super::ParameterStrategy;
+
+        pub struct ArgumentIterator<'a> {
+            strategy: &'a ParameterStrategy,
+            chars: Vec<char>,
+            index: usize,
+        }
+
+        impl<'a> Iterator for ArgumentIterator<'a> {
+            type Item = String;
+
+            fn next(&mut self) -> Option<String> {
+                let (index, result) = parse_whole_string(&self.chars, self.index, &self.strategy);
+                self.index = index;
+                result
+            }
+        }
+
+        pub fn parse<'a>(string: &str, strategy: &'a ParameterStrategy) -> ArgumentIterator<'a> {
+            ArgumentIterator { strategy, chars: string.chars().collect(), index: 0 }
+        }
+
+        fn parse_whole_string(chars: &Vec<char>, original_pos: usize, strategy: &ParameterStrategy) -> (usize, Option<String>) {
+            let mut index = original_pos;
+            let mut result: String = String::new();
+            while let Some(c) = chars.get(index) { if c.is_whitespace() { index += 1; } else { break; } }
             loop {
                 match chars.get(index) {
-                    Some(&'{') => {
+                    None => return (index, Some(result)),
+                    Some(c) if c.is_whitespace() => return (index, Some(result)),
+                    Some(&'$') => match parse_dollar_parameters(chars, index, strategy) {
+                        (i, None) => return (i, None),
+                        (i, Some(string)) => {
+                            result.push_str(&string);
+                            index = i;
+                        }
+                    }
+                    Some(&'\'') => match parse_single_quote(chars, index, strategy) {
+                        (i, None) => return (i, None),
+                        (i, Some(string)) => {
+                            result.push_str(&string);
+                            index = i;
+                        }
+                    }
+                    Some(&'\"') => match parse_double_quote(chars, index, strategy) {
+                        (i, None) => return (i, None),
+                        (i, Some(string)) => {
+                            result.push_str(&string);
+                            index = i;
+                        }
+                    }
+                    Some(&'\\') => {
                         index += 1;
-                        loop {
-                            if let Some(c) = chars.get(index) {
-                                if c == &'}' { return (index + 1, Some(b.as_ref()(result))); }
+                        match chars.get(index) {
+                            Some(c @ &'\n') | Some(c @ &'\r') => {
+                                if let &ParameterStrategy::Ignore = strategy {
+                                    result.push('\\');
+                                    result.push(c.clone());
+                                }
+                                index += 1;
+                            }
+                            Some(c) => {
+                                if let &ParameterStrategy::Ignore = strategy { result.push('\\') }
+                                index += 1;
                                 result.push(c.clone());
+                            }
+                            None => return (original_pos, None)
+                        }
+                    }
+                    Some(c) => {
+                        index += 1;
+                        result.push(c.clone());
+                    }
+                }
+            }
+        }
+
+        fn parse_single_quote(chars: &Vec<char>, pos: usize, strategy: &ParameterStrategy) -> (usize, Option<String>) {
+            let mut index = pos;
+            let mut result: String = String::new();
+            if let &ParameterStrategy::Ignore = strategy { result.push('\'') }
+            index += 1;
+            loop {
+                if let Some(c) = chars.get(index) {
+                    if c == &'\'' {
+                        if let &ParameterStrategy::Ignore = strategy { result.push('\'') }
+                        index += 1;
+                        return (index, Some(result));
+                    }
+                    result.push(c.clone());
+                    index += 1;
+                } else {
+                    return (pos, None);
+                }
+            }
+        }
+
+        fn parse_double_quote(chars: &Vec<char>, pos: usize, strategy: &ParameterStrategy) -> (usize, Option<String>) {
+            let mut index = pos;
+            let mut result: String = String::new();
+            if let &ParameterStrategy::Ignore = strategy { result.push('\"') }
+            index += 1;
+            loop {
+                match chars.get(index) {
+                    Some(&'\\') => {
+                        index += 1;
+                        match chars.get(index) {
+                            Some(c @ &'\"') | Some(c @ &'$') => {
+                                if let &ParameterStrategy::Ignore = strategy { result.push('\\') }
                                 index += 1;
-                            } else {
-                                return (pos, None);
+                                result.push(c.clone());
                             }
+                            Some(c @ &'\n') | Some(c @ &'\r') => {
+                                if let &ParameterStrategy::Ignore = strategy {
+                                    result.push('\\');
+                                    result.push(c.clone());
+                                }
+                                index += 1;
+                            }
+                            Some(&_) => result.push('\\'),
+                            None => return (pos, None)
                         }
                     }
-                    Some(c @ &'a' ... 'z') | Some(c @ &'A' ... 'Z') |
-                    Some(c @ &'0' ... '9') | Some(c @ &'_') => {
+                    Some(&'\"') => {
+                        if let &ParameterStrategy::Ignore = strategy { result.push('\"') }
+                        index += 1;
+                        return (index, Some(result));
+                    }
+                    Some(&'$') => match parse_dollar_parameters(chars, index, strategy) {
+                        (i, None) => return (i, None),
+                        (i, Some(string)) => {
+                            result.push_str(&string);
+                            index = i;
+                        }
+                    }
+                    Some(c) => {
                         result.push(c.clone());
                         index += 1;
                     }
-                    _ if result.is_empty() => return (pos + 1, Some("$".to_owned())),
-                    _ => return (index, Some(b.as_ref()(result)))
+                    None => return (pos, None)
+                }
+            }
+        }
+
+        fn parse_dollar_parameters(chars: &Vec<char>, pos: usize, strategy: &ParameterStrategy) -> (usize, Option<String>) {
+            match strategy {
+                &ParameterStrategy::Ignore => return (pos + 1, Some("$".to_owned())),
+                &ParameterStrategy::Map(ref b) => {
+                    let mut index = pos + 1;
+                    let mut result = String::new();
+                    loop {
+                        match chars.get(index) {
+                            Some(&'{') => {
+                                index += 1;
+                                loop {
+                                    if let Some(c) = chars.get(index) {
+                                        if c == &'}' { return (index + 1, Some(map_braced_parameter(b.as_ref(), result))); }
+                                        result.push(c.clone());
+                                        index += 1;
+                                    } else {
+                                        return (pos, None);
+                                    }
+                                }
+                            }
+                            Some(c @ &('a'..='z')) | Some(c @ &('A'..='Z')) |
+                            Some(c @ &('0'..='9')) | Some(c @ &'_') => {
+                                result.push(c.clone());
+                                index += 1;
+                            }
+                            _ if result.is_empty() => return (pos + 1, Some("$".to_owned())),
+                            _ => return (index, Some(b.as_ref()(result)))
+                        }
+                    }
                 }
             }
         }
+
+        fn map_braced_parameter(function: &dyn Fn(String) -> String, parameter: String) -> String {
+            match parameter.find(":-") {
+                Some(i) => {
+                    let mapped = function(parameter[..i].to_owned());
+                    if mapped.is_empty() { parameter[i + 2..].to_owned() } else { mapped }
+                }
+                None => function(parameter)
+            }
+        }
+    }
+
+    #[test]
+    fn parse_matches_previous_implementation() {
+        let inputs = [
+            "", "   ", "--username ${auth_player_name} --version ${version_name}",
+            "$ $$ $_ $9lives ${missing:-fallback} ${name:-unused} ${} $abc_1-rest trailing$",
+            "$ab{c} $ab{c}d x$ab{missing:-fallback}", "$a{", "${}", "\"$ab{c}\"",
+            "'single quoted' 'it''s' '' \"\" \"double $name ${name}\" \"\\$escaped \\\"quote\\\" \\n\"",
+            "\\\"escaped\\\" a\\ b \\$name line\\\ncontinued \"line\\\ncontinued\"",
+            "-Dunicode=\u{e4}\u{3b1}\u{4e2d}\u{1f600} \t\n  tab\tseparated\u{3000}ideographic",
+            "mixed'single'\"double\"$name${name}done",
+            "\"unterminated", "'unterminated", "ok ${unterminated", "ok \"${unterminated\"", "trailing\\",
+            "first \"second\" 'third' \"unterminated",
+        ];
+        for strategy in &[ParameterStrategy::ignore(), strategy()] {
+            for input in inputs.iter() {
+                let current: Vec<String> = parse(input, strategy).literal_backslashes(false).take(16).collect();
+                let previous: Vec<String> = previous::parse(input, strategy).take(16).collect();
+                assert_eq!(current, previous, "input {:?}", input);
+            }
+        }
     }
 }
@@ -0,0 +1,174 @@
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use std::collections::{HashMap, VecDeque};
+
+const STATS_WINDOW_MILLIS: u64 = 3000;
+const STATS_INTERVAL_MILLIS: u64 = 500;
+
+#[derive(Clone, Debug)]
+pub enum ProgressEvent {
+    FileStarted { path: PathBuf, size: Option<u64> },
+    BytesTransferred { path: PathBuf, bytes: u64 },
+    FileFinished { path: PathBuf },
+    ExtractEntry { name: String },
+    Stats(DownloadStats),
+}
+
+#[derive(Clone, Debug)]
+pub struct DownloadStats {
+    bytes_per_second: f64,
+    bytes_transferred: u64,
+    files_remaining: usize,
+    eta: Option<Duration>,
+}
+
+pub struct DownloadStatsTracker {
+    samples: VecDeque<(Instant, u64)>,
+    files: HashMap<PathBuf, (Option<u64>, u64)>,
+    files_total: usize,
+    files_finished: usize,
+    bytes_transferred: u64,
+    last_emitted: Option<Instant>,
+}
+
+pub trait ProgressListener {
+    fn on_progress(&mut self, event: &ProgressEvent);
+}
+
+impl<F: FnMut(&ProgressEvent)> ProgressListener for F {
+    fn on_progress(&mut self, event: &ProgressEvent) {
+        self(event)
+    }
+}
+
+impl DownloadStats {
+    pub fn bytes_per_second(&self) -> f64 {
+        self.bytes_per_second
+    }
+
+    pub fn bytes_transferred(&self) -> u64 {
+        self.bytes_transferred
+    }
+
+    pub fn files_remaining(&self) -> usize {
+        self.files_remaining
+    }
+
+    pub fn eta(&self) -> Option<Duration> {
+        self.eta
+    }
+}
+
+impl DownloadStatsTracker {
+    pub fn new(files_total: usize) -> DownloadStatsTracker {
+        DownloadStatsTracker {
+            samples: VecDeque::new(),
+            files: HashMap::new(),
+            files_total,
+            files_finished: 0,
+            bytes_transferred: 0,
+            last_emitted: None,
+        }
+    }
+
+    pub fn update(&mut self, event: &ProgressEvent) -> Option<DownloadStats> {
+        self.update_at(event, Instant::now())
+    }
+
+    fn update_at(&mut self, event: &ProgressEvent, now: Instant) -> Option<DownloadStats> {
+        match *event {
+            ProgressEvent::FileStarted { ref path, size } => {
+                // a retried file starts over, so forget what was counted for it
+                self.files.insert(path.clone(), (size, 0));
+            }
+            ProgressEvent::BytesTransferred { ref path, bytes } => {
+                self.files.entry(path.clone()).or_insert((None, 0)).1 += bytes;
+                self.bytes_transferred += bytes;
+                self.samples.push_back((now, bytes));
+            }
+            ProgressEvent::FileFinished { .. } => self.files_finished += 1,
+            _ => return None,
+        }
+        let window = Duration::from_millis(STATS_WINDOW_MILLIS);
+        while self.samples.front().is_some_and(|&(time, _)| now.duration_since(time) > window) {
+            self.samples.pop_front();
+        }
+        let due = self.last_emitted.is_none_or(|time| now.duration_since(time) >= Duration::from_millis(STATS_INTERVAL_MILLIS));
+        let finished = self.files_finished >= self.files_total;
+        if due || finished {
+            self.last_emitted = Some(now);
+            Some(self.stats_at(now))
+        } else {
+            None
+        }
+    }
+
+    pub fn stats(&self) -> DownloadStats {
+        self.stats_at(Instant::now())
+    }
+
+    fn stats_at(&self, now: Instant) -> DownloadStats {
+        let bytes_per_second = match self.samples.front() {
+            Some(&(start, _)) => {
+                let elapsed = now.duration_since(start);
+                let seconds = elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 / 1e9;
+                let bytes: u64 = self.samples.iter().map(|&(_, bytes)| bytes).sum();
+                // a window shorter than a second would make the first few samples look far too fast
+                bytes as f64 / seconds.max(1.0)
+            }
+            None => 0.0
+        };
+        let files_remaining = self.files_total.saturating_sub(self.files_finished);
+        let known: Vec<u64> = self.files.values().filter_map(|&(size, _)| size).collect();
+        let eta = if bytes_per_second > 0.0 && !known.is_empty() {
+            // files that have not started yet are assumed to be as large as the average started one
+            let remaining_started: u64 = self.files.values().map(|&(size, done)| size.unwrap_or(done).saturating_sub(done)).sum();
+            let average = known.iter().sum::<u64>() / known.len() as u64;
+            let not_started = self.files_total.saturating_sub(self.files.len());
+            let remaining = remaining_started + average * not_started as u64;
+            Some(Duration::from_millis((remaining as f64 / bytes_per_second * 1000.0) as u64))
+        } else {
+            None
+        };
+        DownloadStats { bytes_per_second, bytes_transferred: self.bytes_transferred, files_remaining, eta }
+    }
+}
+
+pub fn notify(listener: &mut Option<&mut dyn ProgressListener>, event: ProgressEvent) {
+    if let Some(ref mut listener) = *listener {
+        listener.on_progress(&event);
+    }
+}
+
+pub fn reborrow<'a>(listener: &'a mut Option<&mut dyn ProgressListener>) -> Option<&'a mut dyn ProgressListener> {
+    match *listener {
+        Some(ref mut listener) => Some(&mut **listener),
+        None => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn download_stats() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut tracker = DownloadStatsTracker::new(2);
+        let path = PathBuf::from("a");
+        tracker.update_at(&ProgressEvent::FileStarted { path: path.clone(), size: Some(4000) }, at(0));
+        tracker.update_at(&ProgressEvent::BytesTransferred { path: path.clone(), bytes: 1000 }, at(0));
+        assert!(tracker.update_at(&ProgressEvent::BytesTransferred { path: path.clone(), bytes: 1000 }, at(100)).is_none());
+        let stats = tracker.update_at(&ProgressEvent::BytesTransferred { path: path.clone(), bytes: 1000 }, at(2000)).unwrap();
+        assert_eq!(stats.bytes_transferred(), 3000);
+        assert_eq!(stats.bytes_per_second(), 1500.0);
+        assert_eq!(stats.files_remaining(), 2);
+        // 1000 bytes left in the started file plus an unstarted one of the average size
+        assert_eq!(stats.eta(), Some(Duration::from_millis(3333)));
+        let stats = tracker.update_at(&ProgressEvent::BytesTransferred { path: path.clone(), bytes: 1000 }, at(6000)).unwrap();
+        assert_eq!(stats.bytes_per_second(), 1000.0);
+    }
+}
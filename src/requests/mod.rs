@@ -1,30 +1,98 @@
 #![allow(dead_code)]
 
+use std::fs;
 use std::fmt;
+use std::mem;
 use std::error;
+use std::rc::Rc;
+use std::io::{Read, Write};
+use std::cell::RefCell;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::path::{Path, PathBuf};
 use std::result::Result;
-use std::collections::HashMap;
 
 use uuid::Uuid;
 use serde_json;
 use hyper::error::UriError;
-use hyper::client::FutureResponse;
-use hyper::header::{ContentType, ContentLength};
-use hyper::{Client, Method, Request, Error as HyperError};
+use hyper::client::{FutureResponse, HttpConnector};
+use hyper::header::{ByteRangeSpec, ContentType, ContentLength, Headers, Range, UserAgent};
+use hyper::{Client, Method, Request, StatusCode, Error as HyperError};
+#[cfg(feature = "tls")]
 use hyper_tls::HttpsConnector;
 use tokio_core::reactor::{Core, Handle};
-use futures::{Poll, Future, Stream, IntoFuture};
+use futures::{future, stream, Poll, Future, Stream, IntoFuture};
 
+use checksum::{Checksum, HashAlgo, Hasher};
+use mirrors;
+use progress::{self, ProgressEvent, ProgressListener};
 use versions;
 use yggdrasil;
 
 #[derive(Debug)]
 pub enum Error {
     UnrecognizedJson(String),
-    NetworkIOError(Box<error::Error + Send + Sync>),
+    NetworkIOError(Box<dyn error::Error + Send + Sync>),
+    Http { status: u16, body: String, error: Option<String>, error_message: Option<String> },
+    ChecksumMismatch { expected: String, actual: String },
+    DeserializeError { url: String, error: serde_json::Error },
+    ForeignCore,
+    Cancelled,
 }
 
-pub struct RequestFuture<T>(Box<Future<Item=T, Error=Error>>);
+pub struct RequestFuture<T>(Box<dyn Future<Item=T, Error=Error>>);
+
+pub struct RequestStream<T>(Box<dyn Stream<Item=T, Error=Error>>);
+
+#[derive(Clone, Debug)]
+pub struct DownloadTask {
+    url: String,
+    path: PathBuf,
+    checksum: Option<Checksum>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+#[derive(Clone)]
+pub struct RequestClient {
+    user_agent: String,
+    headers: Headers,
+    connector: Option<ConnectorFactory>,
+    client: Option<(Client<Connector>, Handle)>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct ManifestValidator {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_modified: Option<String>,
+}
+
+struct PartialFile {
+    path: PathBuf,
+    part_path: PathBuf,
+    file: Option<fs::File>,
+    hasher: Hasher,
+    cancel: CancelToken,
+}
+
+// without the tls feature only plain http mirrors can be reached
+#[cfg(feature = "tls")]
+pub type Connector = HttpsConnector<HttpConnector>;
+
+#[cfg(not(feature = "tls"))]
+pub type Connector = HttpConnector;
+
+pub type ConnectorFactory = Rc<dyn Fn(&Handle) -> Connector>;
+
+const DOWNLOAD_CONCURRENCY: usize = 8;
+
+const MANIFEST_CACHE_FILE: &str = "version_manifest.json";
+const MANIFEST_VALIDATOR_FILE: &str = "version_manifest.validator.json";
+
+pub const DEFAULT_USER_AGENT: &str = concat!("RMCLL/", env!("CARGO_PKG_VERSION"));
 
 impl From<serde_json::Error> for Error {
     fn from(e: serde_json::Error) -> Self {
@@ -44,15 +112,43 @@ impl From<HyperError> for Error {
     }
 }
 
+impl From<::std::io::Error> for Error {
+    fn from(e: ::std::io::Error) -> Self {
+        Error::NetworkIOError(Box::new(e))
+    }
+}
+
+impl error::Error for Error {}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Error::UnrecognizedJson(ref s) => fmt::Display::fmt(s, f),
             Error::NetworkIOError(ref e) => fmt::Display::fmt(e, f),
+            Error::Http { status, error: Some(ref e), error_message: Some(ref m), .. } => {
+                write!(f, "HTTP {}: {}: {}", status, e, m)
+            }
+            Error::Http { status, ref body, .. } => write!(f, "HTTP {}: {}", status, body),
+            Error::ChecksumMismatch { ref expected, ref actual } => {
+                write!(f, "checksum mismatch: expected {}, got {}", expected, actual)
+            }
+            Error::DeserializeError { ref url, ref error } => write!(f, "unrecognized version json from {}: {}", url, error),
+            Error::ForeignCore => write!(f, "the prebuilt client belongs to another core"),
+            Error::Cancelled => write!(f, "operation cancelled"),
         }
     }
 }
 
+impl Error {
+    fn from_http_response(status: u16, body: &[u8]) -> Error {
+        let body = String::from_utf8_lossy(body).into_owned();
+        let json = serde_json::from_str::<serde_json::Value>(&body).unwrap_or(serde_json::Value::Null);
+        let error = json["error"].as_str().map(String::from);
+        let error_message = json["errorMessage"].as_str().map(String::from);
+        Error::Http { status, body, error, error_message }
+    }
+}
+
 impl<T> RequestFuture<T> {
     fn new<F: Future<Item=T, Error=Error> + 'static>(future: F) -> RequestFuture<T> {
         RequestFuture(Box::new(future))
@@ -68,97 +164,905 @@ impl<T> Future for RequestFuture<T> {
     }
 }
 
-fn make_json_https_request(handle: Handle,
-                           url: &str,
-                           json_value: serde_json::Value) -> Result<FutureResponse, Error> {
-    let connector = HttpsConnector::new(4, &handle).unwrap();
-    let client = Client::configure().connector(connector).keep_alive(true).build(&handle);
+impl<T> RequestStream<T> {
+    fn new<S: Stream<Item=T, Error=Error> + 'static>(stream: S) -> RequestStream<T> {
+        RequestStream(Box::new(stream))
+    }
+}
 
-    let request = match json_value {
-        serde_json::Value::Null => Request::new(Method::Get, url.parse()?),
-        _ => {
-            let json = json_value.to_string();
-            let mut req = Request::new(Method::Post, url.parse()?);
-            req.headers_mut().set(ContentType::json());
-            req.headers_mut().set(ContentLength(json.len() as u64));
-            req.set_body(json);
-            req
+impl<T> Stream for RequestStream<T> {
+    type Item = T;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        self.0.as_mut().poll()
+    }
+}
+
+impl DownloadTask {
+    pub fn new(url: String, path: PathBuf, sha1: Option<String>) -> DownloadTask {
+        DownloadTask::with_checksum(url, path, sha1.map(Checksum::sha1))
+    }
+
+    pub fn with_checksum(url: String, path: PathBuf, checksum: Option<Checksum>) -> DownloadTask {
+        DownloadTask { url, path, checksum }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn sha1(&self) -> Option<&str> {
+        match self.checksum {
+            Some(ref checksum) if checksum.algo() == HashAlgo::Sha1 => Some(checksum.value()),
+            _ => None
         }
-    };
+    }
 
-    Result::Ok(client.request(request))
+    pub fn checksum(&self) -> Option<&Checksum> {
+        self.checksum.as_ref()
+    }
 }
 
-fn make_json_request(handle: Handle,
-                     url: &str,
-                     json_value: serde_json::Value) -> RequestFuture<serde_json::Value> {
-    RequestFuture::new(make_json_https_request(handle, url, json_value).into_future().and_then(|req| {
-        req.map_err(Error::from).and_then(|res| {
-            res.body().concat2().map_err(Error::from).and_then(|body| {
-                serde_json::from_slice(&body).map_err(Error::from).into_future()
+impl Default for RequestClient {
+    fn default() -> Self {
+        RequestClient::new()
+    }
+}
+
+impl RequestClient {
+    pub fn new() -> RequestClient {
+        RequestClient { user_agent: DEFAULT_USER_AGENT.to_owned(), headers: Headers::new(), connector: None, client: None }
+    }
+
+    // a connector keeps the handle it was built with, so custom root certificates are given as a factory
+    // that builds one for every core the requests run on, including the blocking wrappers' own cores
+    pub fn with_connector<F: Fn(&Handle) -> Connector + 'static>(mut self, factory: F) -> Self {
+        self.connector = Some(Rc::new(factory));
+        self
+    }
+
+    // a prebuilt client only makes progress on the core it was built for, requests run on any other
+    // core (e.g. by the blocking wrappers) fail with ForeignCore rather than losing its configuration
+    pub fn with_client(mut self, client: Client<Connector>, handle: &Handle) -> Self {
+        self.client = Some((client, handle.clone()));
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_owned();
+        self
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.set_raw(name.to_owned(), value.to_owned());
+        self
+    }
+
+    pub fn user_agent(&self) -> &str {
+        &self.user_agent
+    }
+
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    fn request(&self, handle: &Handle, mut request: Request) -> Result<FutureResponse, Error> {
+        let client = match (&self.client, &self.connector) {
+            (&Some((ref client, ref client_handle)), _) if client_handle.id() == handle.id() => client.clone(),
+            (&Some(_), _) => return Result::Err(Error::ForeignCore),
+            (&None, Some(factory)) => Client::configure().connector(factory(handle)).keep_alive(true).build(handle),
+            (&None, &None) => {
+                let connector = default_connector(handle);
+                Client::configure().connector(connector).keep_alive(true).build(handle)
+            }
+        };
+
+        request.headers_mut().set(UserAgent::new(self.user_agent.clone()));
+        request.headers_mut().extend(self.headers.iter());
+        Result::Ok(client.request(request))
+    }
+
+    fn json_https_request(&self,
+                          handle: &Handle,
+                          url: &str,
+                          json_value: serde_json::Value) -> Result<FutureResponse, Error> {
+        let request = match json_value {
+            serde_json::Value::Null => Request::new(Method::Get, url.parse()?),
+            _ => {
+                let json = json_value.to_string();
+                let mut req = Request::new(Method::Post, url.parse()?);
+                req.headers_mut().set(ContentType::json());
+                req.headers_mut().set(ContentLength(json.len() as u64));
+                req.set_body(json);
+                req
+            }
+        };
+
+        self.request(handle, request)
+    }
+
+    fn json_request(&self,
+                    handle: &Handle,
+                    url: &str,
+                    json_value: serde_json::Value) -> RequestFuture<serde_json::Value> {
+        RequestFuture::new(self.json_https_request(handle, url, json_value).into_future().and_then(|req| {
+            req.map_err(Error::from).and_then(|res| {
+                let status = res.status();
+                res.body().concat2().map_err(Error::from).and_then(move |body| {
+                    if !status.is_success() {
+                        return Result::Err(Error::from_http_response(status.as_u16(), &body)).into_future();
+                    }
+                    serde_json::from_slice(&body).map_err(Error::from).into_future()
+                })
+            })
+        }))
+    }
+
+    fn ranged_download_request(&self, handle: &Handle, url: &str, from: u64) -> Result<FutureResponse, Error> {
+        let mut request = Request::new(Method::Get, url.parse()?);
+        if from > 0 { request.headers_mut().set(Range::Bytes(vec![ByteRangeSpec::AllFrom(from)])) }
+        self.request(handle, request)
+    }
+
+    fn download_events(&self, handle: &Handle, task: DownloadTask, cancel: CancelToken) -> RequestStream<ProgressEvent> {
+        let from = PartialFile::existing_len(task.path());
+        let req = cancel.check().and_then(|_| self.ranged_download_request(handle, &task.url, from));
+        let (client, handle) = (self.clone(), handle.clone());
+        RequestStream::new(req.into_future().and_then(|req| req.map_err(Error::from)).map(move |res| {
+            let status = res.status();
+            if status == StatusCode::RangeNotSatisfiable {
+                // the .part file is unusable, so discard it and download from scratch
+                if let Err(e) = fs::remove_file(PartialFile::part_path_of(task.path())) {
+                    return RequestStream::new(stream::once(Result::Err(Error::from(e))));
+                }
+                return client.download_events(&handle, task, cancel);
+            }
+            if !status.is_success() {
+                return RequestStream::new(res.body().concat2().map_err(Error::from).and_then(move |body| {
+                    Result::Err(Error::from_http_response(status.as_u16(), &body))
+                }).into_stream());
+            }
+            let resume = from > 0 && status == StatusCode::PartialContent;
+            let size = res.headers().get::<ContentLength>().map(|&ContentLength(size)| size);
+            let DownloadTask { path, checksum, .. } = task;
+            let algo = checksum.as_ref().map_or(HashAlgo::Sha1, Checksum::algo);
+            let state: Rc<RefCell<Option<PartialFile>>> = Rc::new(RefCell::new(None));
+            let (start_state, chunk_state, finish_state) = (state.clone(), state.clone(), state);
+            let (start_path, chunk_path, finish_path) = (path.clone(), path.clone(), path);
+            let (start_cancel, chunk_cancel) = (cancel.clone(), cancel);
+            let start = future::lazy(move || {
+                start_cancel.check()?;
+                *start_state.borrow_mut() = Some(PartialFile::open(start_path.clone(), algo, resume, start_cancel)?);
+                Result::Ok(ProgressEvent::FileStarted { path: start_path, size })
+            });
+            let chunks = res.body().map_err(Error::from).and_then(move |chunk| {
+                chunk_cancel.check()?;
+                if let Some(ref mut partial) = *chunk_state.borrow_mut() { partial.write_all(&chunk)? }
+                Result::Ok(ProgressEvent::BytesTransferred { path: chunk_path.clone(), bytes: chunk.len() as u64 })
+            });
+            let finish = future::lazy(move || {
+                if let Some(partial) = finish_state.borrow_mut().take() { partial.finish(checksum)? }
+                Result::Ok(ProgressEvent::FileFinished { path: finish_path })
+            });
+            RequestStream::new(start.into_stream().chain(chunks).chain(finish.into_stream()))
+        }).flatten_stream())
+    }
+
+    pub fn download_to_file_async(&self,
+                                  handle: &Handle,
+                                  url: &str,
+                                  path: &Path,
+                                  sha1: Option<&str>) -> RequestFuture<()> {
+        let task = DownloadTask::new(url.to_owned(), path.to_path_buf(), sha1.map(String::from));
+        RequestFuture::new(self.download_events(handle, task, CancelToken::new()).for_each(|_| Result::Ok(())))
+    }
+
+    pub fn download_to_file(&self, url: &str, path: &Path, sha1: Option<&str>) -> Result<(), Error> {
+        let mut core = Core::new().unwrap();
+        let req = self.download_to_file_async(&core.handle(), url, path, sha1);
+        core.run(req)
+    }
+
+    pub fn download_all_async(&self,
+                              handle: &Handle,
+                              tasks: Vec<DownloadTask>,
+                              cancel: &CancelToken) -> RequestStream<ProgressEvent> {
+        let (client, handle) = (self.clone(), handle.clone());
+        let cancel = cancel.clone();
+        let groups: Vec<Vec<DownloadTask>> = tasks.chunks(DOWNLOAD_CONCURRENCY).map(|c| c.to_vec()).collect();
+        RequestStream::new(stream::iter_ok::<_, Error>(groups).and_then(move |group| {
+            cancel.check()?;
+            Result::Ok(group.into_iter().fold(RequestStream::new(stream::empty::<ProgressEvent, Error>()), |events, task| {
+                RequestStream::new(events.select(client.download_events(&handle, task, cancel.clone())))
+            }))
+        }).flatten())
+    }
+
+    pub fn download_all(&self,
+                        tasks: &[DownloadTask],
+                        progress: Option<&mut dyn ProgressListener>,
+                        cancel: Option<&CancelToken>) -> Result<(), Error> {
+        let mut core = Core::new().unwrap();
+        let mut progress = progress;
+        let cancel = cancel.cloned().unwrap_or_default();
+        let mut stats = progress::DownloadStatsTracker::new(tasks.len());
+        let events = self.download_all_async(&core.handle(), tasks.to_vec(), &cancel);
+        core.run(events.for_each(|event| {
+            let update = stats.update(&event);
+            progress::notify(&mut progress, event);
+            if let Some(update) = update { progress::notify(&mut progress, ProgressEvent::Stats(update)) }
+            Result::Ok(())
+        }))
+    }
+
+    pub fn authenticate_async(&self,
+                              handle: &Handle,
+                              mirror: &mirrors::MirrorConfig,
+                              username: &str,
+                              password: &str,
+                              client_token: &Uuid) -> RequestFuture<(String, yggdrasil::Profile)> {
+        let url = mirror.auth_server_url("authenticate");
+        let req = self.json_request(handle, &url, json!({
+            "username": username,
+            "password": password,
+            "clientToken": client_token.simple().to_string(),
+            "agent": { "name": "Minecraft", "version": 1 }
+        }));
+
+        RequestFuture::new(req.and_then(|json| {
+            let error = || Error::UnrecognizedJson(json.to_string());
+            let profile = parse_profile(&json["selectedProfile"]).ok_or_else(error)?;
+            let access_token = json["accessToken"].as_str().ok_or_else(error)?.to_owned();
+            Result::Ok((access_token, profile))
+        }))
+    }
+
+    pub fn authenticate(&self,
+                        mirror: &mirrors::MirrorConfig,
+                        username: &str,
+                        password: &str,
+                        client_token: &Uuid) -> Result<(String, yggdrasil::Profile), Error> {
+        let mut core = Core::new().unwrap();
+        let req = self.authenticate_async(&core.handle(), mirror, username, password, client_token);
+        core.run(req)
+    }
+
+    pub fn refresh_async(&self,
+                         handle: &Handle,
+                         mirror: &mirrors::MirrorConfig,
+                         access_token: &str,
+                         client_token: &Uuid) -> RequestFuture<(String, yggdrasil::Profile)> {
+        let url = mirror.auth_server_url("refresh");
+        let req = self.json_request(handle, &url, json!({
+            "accessToken": access_token,
+            "clientToken": client_token.simple().to_string()
+        }));
+
+        RequestFuture::new(req.and_then(|json| {
+            let error = || Error::UnrecognizedJson(json.to_string());
+            let profile = parse_profile(&json["selectedProfile"]).ok_or_else(error)?;
+            let access_token = json["accessToken"].as_str().ok_or_else(error)?.to_owned();
+            Result::Ok((access_token, profile))
+        }))
+    }
+
+    pub fn refresh(&self,
+                   mirror: &mirrors::MirrorConfig,
+                   access_token: &str,
+                   client_token: &Uuid) -> Result<(String, yggdrasil::Profile), Error> {
+        let mut core = Core::new().unwrap();
+        let req = self.refresh_async(&core.handle(), mirror, access_token, client_token);
+        core.run(req)
+    }
+
+    pub fn validate_async(&self,
+                          handle: &Handle,
+                          mirror: &mirrors::MirrorConfig,
+                          access_token: &str,
+                          client_token: &Uuid) -> RequestFuture<bool> {
+        let url = mirror.auth_server_url("validate");
+        let req = self.json_https_request(handle, &url, json!({
+            "accessToken": access_token,
+            "clientToken": client_token.simple().to_string()
+        }));
+
+        RequestFuture::new(req.into_future().and_then(|req| {
+            req.map_err(Error::from).and_then(|res| {
+                let status = res.status();
+                res.body().concat2().map_err(Error::from).and_then(move |body| {
+                    match status.as_u16() {
+                        204 => Result::Ok(true),
+                        403 => Result::Ok(false),
+                        code => Result::Err(Error::from_http_response(code, &body)),
+                    }
+                })
+            })
+        }))
+    }
+
+    pub fn validate(&self,
+                    mirror: &mirrors::MirrorConfig,
+                    access_token: &str,
+                    client_token: &Uuid) -> Result<bool, Error> {
+        let mut core = Core::new().unwrap();
+        let req = self.validate_async(&core.handle(), mirror, access_token, client_token);
+        core.run(req)
+    }
+
+    pub fn has_joined_async(&self,
+                            handle: &Handle,
+                            mirror: &mirrors::MirrorConfig,
+                            username: &str,
+                            server_hash: &str) -> RequestFuture<Option<yggdrasil::Profile>> {
+        let url = mirror.session_server_url(&format!("session/minecraft/hasJoined?username={}&serverId={}",
+                                                     encode_query_component(username),
+                                                     encode_query_component(server_hash)));
+        let req = self.json_https_request(handle, &url, serde_json::Value::Null);
+
+        RequestFuture::new(req.into_future().and_then(|req| {
+            req.map_err(Error::from).and_then(|res| {
+                let status = res.status();
+                res.body().concat2().map_err(Error::from).and_then(move |body| {
+                    match status.as_u16() {
+                        // the session server answers an unauthenticated join with an empty response
+                        204 => Result::Ok(None),
+                        200 if body.is_empty() => Result::Ok(None),
+                        200 => {
+                            let json: serde_json::Value = serde_json::from_slice(&body)?;
+                            parse_profile(&json).map(Some).ok_or_else(|| Error::UnrecognizedJson(json.to_string()))
+                        }
+                        code => Result::Err(Error::from_http_response(code, &body)),
+                    }
+                })
+            })
+        }))
+    }
+
+    pub fn has_joined(&self,
+                      mirror: &mirrors::MirrorConfig,
+                      username: &str,
+                      server_hash: &str) -> Result<Option<yggdrasil::Profile>, Error> {
+        let mut core = Core::new().unwrap();
+        let req = self.has_joined_async(&core.handle(), mirror, username, server_hash);
+        core.run(req)
+    }
+
+    pub fn versions_async(&self, handle: &Handle, mirror: &mirrors::MirrorConfig) -> RequestFuture<serde_json::Value> {
+        let url = mirror.launcher_meta_url("mc/game/version_manifest.json");
+
+        self.json_request(handle, &url, serde_json::Value::Null)
+    }
+
+    pub fn versions(&self, mirror: &mirrors::MirrorConfig) -> Result<serde_json::Value, Error> {
+        let mut core = Core::new().unwrap();
+        let req = self.versions_async(&core.handle(), mirror);
+        core.run(req)
+    }
+
+    pub fn version_manifest_async(&self,
+                                  handle: &Handle,
+                                  mirror: &mirrors::MirrorConfig) -> RequestFuture<versions::VersionManifest> {
+        RequestFuture::new(self.versions_async(handle, mirror).and_then(|json| {
+            serde_json::from_value(json.clone()).map_err(|_| Error::UnrecognizedJson(json.to_string()))
+        }))
+    }
+
+    pub fn version_manifest(&self, mirror: &mirrors::MirrorConfig) -> Result<versions::VersionManifest, Error> {
+        let mut core = Core::new().unwrap();
+        let req = self.version_manifest_async(&core.handle(), mirror);
+        core.run(req)
+    }
+
+    // sends the stored ETag and Last-Modified so an unchanged manifest is answered with 304 and read from the cache
+    pub fn version_manifest_cached_async(&self,
+                                         handle: &Handle,
+                                         mirror: &mirrors::MirrorConfig,
+                                         cache_dir: &Path) -> RequestFuture<versions::VersionManifest> {
+        let cache_path = cache_dir.join(MANIFEST_CACHE_FILE);
+        let validator_path = cache_dir.join(MANIFEST_VALIDATOR_FILE);
+        let validator: ManifestValidator = match (cache_path.is_file(), fs::File::open(validator_path.as_path())) {
+            (true, Result::Ok(file)) => serde_json::from_reader(file).unwrap_or_default(),
+            _ => ManifestValidator::default()
+        };
+        let url = mirror.launcher_meta_url("mc/game/version_manifest.json");
+        let req = url.parse().map_err(Error::from).and_then(|uri| {
+            let mut request = Request::new(Method::Get, uri);
+            if let Some(ref etag) = validator.etag { request.headers_mut().set_raw("If-None-Match", etag.clone()) }
+            if let Some(ref last_modified) = validator.last_modified {
+                request.headers_mut().set_raw("If-Modified-Since", last_modified.clone())
+            }
+            self.request(handle, request)
+        });
+
+        RequestFuture::new(req.into_future().and_then(|req| {
+            req.map_err(Error::from).and_then(move |res| {
+                let status = res.status();
+                let header = |name: &str| res.headers().get_raw(name).and_then(|raw| raw.one())
+                    .and_then(|value| String::from_utf8(value.to_vec()).ok());
+                let validator = ManifestValidator { etag: header("ETag"), last_modified: header("Last-Modified") };
+                res.body().concat2().map_err(Error::from).and_then(move |body| {
+                    let body = match status {
+                        StatusCode::NotModified => fs::read(cache_path.as_path())?,
+                        _ if status.is_success() => body.to_vec(),
+                        _ => return Result::Err(Error::from_http_response(status.as_u16(), &body))
+                    };
+                    let manifest = serde_json::from_slice(&body)
+                        .map_err(|_| Error::UnrecognizedJson(String::from_utf8_lossy(&body).into_owned()))?;
+                    // only a manifest that parsed is cached, and a crash mid-write never leaves a truncated cache
+                    if status.is_success() {
+                        if let Some(parent) = cache_path.parent() { fs::create_dir_all(parent)? }
+                        write_replacing(cache_path.as_path(), &body)?;
+                        write_replacing(validator_path.as_path(), &serde_json::to_vec(&validator)?)?;
+                    }
+                    Result::Ok(manifest)
+                })
+            })
+        }))
+    }
+
+    pub fn version_manifest_cached(&self,
+                                   mirror: &mirrors::MirrorConfig,
+                                   cache_dir: &Path) -> Result<versions::VersionManifest, Error> {
+        let mut core = Core::new().unwrap();
+        let req = self.version_manifest_cached_async(&core.handle(), mirror, cache_dir);
+        core.run(req)
+    }
+
+    pub fn latest_async(&self,
+                        handle: &Handle,
+                        mirror: &mirrors::MirrorConfig,
+                        version_type: versions::VersionType) -> RequestFuture<versions::VersionManifestEntry> {
+        RequestFuture::new(self.version_manifest_async(handle, mirror).and_then(move |manifest| {
+            manifest.latest(version_type).cloned().ok_or_else(|| {
+                Error::UnrecognizedJson(format!("no latest {:?} version in manifest", version_type))
             })
-        })
-    }))
+        }))
+    }
+
+    pub fn latest(&self,
+                  mirror: &mirrors::MirrorConfig,
+                  version_type: versions::VersionType) -> Result<versions::VersionManifestEntry, Error> {
+        let mut core = Core::new().unwrap();
+        let req = self.latest_async(&core.handle(), mirror, version_type);
+        core.run(req)
+    }
+
+    pub fn version_json_async(&self,
+                              handle: &Handle,
+                              mirror: &mirrors::MirrorConfig,
+                              url: &str) -> RequestFuture<serde_json::Value> {
+        self.json_request(handle, &mirror.rewrite(url), serde_json::Value::Null)
+    }
+
+    pub fn version_json(&self, mirror: &mirrors::MirrorConfig, url: &str) -> Result<serde_json::Value, Error> {
+        let mut core = Core::new().unwrap();
+        let req = self.version_json_async(&core.handle(), mirror, url);
+        core.run(req)
+    }
+
+    pub fn deserialize_version_async(&self,
+                                     handle: &Handle,
+                                     mirror: &mirrors::MirrorConfig,
+                                     url: &str) -> RequestFuture<versions::MinecraftVersion> {
+        let url = mirror.rewrite(url);
+        RequestFuture::new(self.json_request(handle, &url, serde_json::Value::Null)
+            .and_then(move |json| deserialize_version(&url, json)))
+    }
+
+    pub fn deserialize_version(&self, mirror: &mirrors::MirrorConfig, url: &str) -> Result<versions::MinecraftVersion, Error> {
+        let mut core = Core::new().unwrap();
+        let req = self.deserialize_version_async(&core.handle(), mirror, url);
+        core.run(req)
+    }
+}
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    fn check(&self) -> Result<(), Error> {
+        if self.is_cancelled() { Result::Err(Error::Cancelled) } else { Result::Ok(()) }
+    }
+}
+
+impl PartialFile {
+    fn part_path_of(path: &Path) -> PathBuf {
+        let mut part_path = path.as_os_str().to_owned();
+        part_path.push(".part");
+        PathBuf::from(part_path)
+    }
+
+    fn existing_len(path: &Path) -> u64 {
+        fs::metadata(PartialFile::part_path_of(path)).map(|m| m.len()).unwrap_or(0)
+    }
+
+    fn open(path: PathBuf, algo: HashAlgo, resume: bool, cancel: CancelToken) -> Result<PartialFile, Error> {
+        let part_path = PartialFile::part_path_of(path.as_path());
+        if let Some(parent) = part_path.parent() { fs::create_dir_all(parent)? }
+        let mut hasher = Hasher::new(algo);
+        let file = if resume {
+            let mut existing = fs::File::open(part_path.as_path())?;
+            let mut buffer = [0u8; 8192];
+            loop {
+                match existing.read(&mut buffer)? {
+                    0 => break,
+                    n => hasher.update(&buffer[..n]),
+                }
+            }
+            fs::OpenOptions::new().append(true).open(part_path.as_path())?
+        } else {
+            fs::File::create(part_path.as_path())?
+        };
+        Result::Ok(PartialFile { path, part_path, file: Some(file), hasher, cancel })
+    }
+
+    fn write_all(&mut self, chunk: &[u8]) -> Result<(), Error> {
+        if let Some(ref mut file) = self.file { file.write_all(chunk)? }
+        self.hasher.update(chunk);
+        Result::Ok(())
+    }
+
+    fn finish(mut self, expected: Option<Checksum>) -> Result<(), Error> {
+        self.file.take();
+        let hasher = mem::replace(&mut self.hasher, Hasher::new(HashAlgo::Sha1));
+        if let Some(expected) = expected {
+            let actual = hasher.hex_digest();
+            if !expected.matches(&actual) {
+                fs::remove_file(self.part_path.as_path())?;
+                return Result::Err(Error::ChecksumMismatch { expected: expected.value().to_owned(), actual });
+            }
+        }
+        fs::rename(self.part_path.as_path(), self.path.as_path())?;
+        Result::Ok(())
+    }
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        // an interrupted download keeps its .part file for resuming, a cancelled one does not
+        self.file.take();
+        if self.cancel.is_cancelled() { let _ = fs::remove_file(self.part_path.as_path()); }
+    }
+}
+
+pub fn download_to_file_async(handle: &Handle,
+                              url: &str,
+                              path: &Path,
+                              sha1: Option<&str>) -> RequestFuture<()> {
+    RequestClient::default().download_to_file_async(handle, url, path, sha1)
+}
+
+pub fn download_to_file(url: &str, path: &Path, sha1: Option<&str>) -> Result<(), Error> {
+    RequestClient::default().download_to_file(url, path, sha1)
+}
+
+pub fn download_blocking(url: &str, path: &Path) -> Result<(), Error> {
+    download_to_file(url, path, None)
+}
+
+pub fn download_all_async(handle: &Handle,
+                          tasks: Vec<DownloadTask>,
+                          cancel: &CancelToken) -> RequestStream<ProgressEvent> {
+    RequestClient::default().download_all_async(handle, tasks, cancel)
+}
+
+pub fn download_all(tasks: &[DownloadTask],
+                    progress: Option<&mut dyn ProgressListener>,
+                    cancel: Option<&CancelToken>) -> Result<(), Error> {
+    RequestClient::default().download_all(tasks, progress, cancel)
+}
+
+pub fn download_all_blocking(tasks: &[DownloadTask]) -> Result<(), Error> {
+    download_all(tasks, None, None)
+}
+
+fn write_replacing(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    let temp_path = PartialFile::part_path_of(path);
+    fs::write(temp_path.as_path(), contents)?;
+    fs::rename(temp_path, path)?;
+    Result::Ok(())
+}
+
+fn encode_query_component(string: &str) -> String {
+    string.bytes().map(|b| match b {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+        _ => format!("%{:02X}", b)
+    }).collect()
+}
+
+#[cfg(feature = "tls")]
+fn default_connector(handle: &Handle) -> Connector {
+    HttpsConnector::new(4, handle).unwrap()
+}
+
+#[cfg(not(feature = "tls"))]
+fn default_connector(handle: &Handle) -> Connector {
+    HttpConnector::new(4, handle)
+}
+
+fn deserialize_version(url: &str, json: serde_json::Value) -> Result<versions::MinecraftVersion, Error> {
+    serde_json::from_value(json).map_err(|error| Error::DeserializeError { url: url.to_owned(), error })
+}
+
+fn parse_profile(json: &serde_json::Value) -> Option<yggdrasil::Profile> {
+    let uuid = Uuid::parse_str(json["id"].as_str()?).ok()?;
+    let name = json["name"].as_str()?.to_owned();
+    let properties = match json["properties"] {
+        serde_json::Value::Null => Vec::new(),
+        ref properties => serde_json::from_value(properties.clone()).ok()?
+    };
+    Some(yggdrasil::Profile::new(uuid, name, properties))
+}
+
+pub fn req_authenticate_async(handle: &Handle,
+                              mirror: &mirrors::MirrorConfig,
+                              username: &str,
+                              password: &str,
+                              client_token: &Uuid) -> RequestFuture<(String, yggdrasil::Profile)> {
+    RequestClient::default().authenticate_async(handle, mirror, username, password, client_token)
 }
 
 pub fn req_authenticate(username: &str,
                         password: &str,
-                        client_token: &Uuid) -> Result<(Uuid, yggdrasil::Profile), Error> {
-    let mut core = Core::new().unwrap();
-
-    let req = make_json_request(core.handle(), "https://authserver.mojang.com/authenticate", json!({
-        "username": username,
-        "password": password,
-        "clientToken": client_token.simple().to_string(),
-        "agent": { "name": "Minecraft", "version": 1 }
-    }));
-
-    core.run(req.map(|json| {
-        let error = || Error::UnrecognizedJson(json.to_string());
-        let uuid = Uuid::parse_str(json["selectedProfile"]["id"].as_str().ok_or(error())?).map_err(|_| error())?;
-        let name = json["selectedProfile"]["name"].as_str().ok_or(error())?.to_owned();
-        let properties = HashMap::new(); // TODO: deserialize properties
-        let access_token_string = json["accessToken"].as_str().ok_or(error())?;
-        let access_token = Uuid::parse_str(access_token_string).map_err(|_| error())?;
-        Result::Ok((access_token, yggdrasil::Profile::new(uuid, name, properties)))
-    }))?
-}
-
-pub fn req_refresh(access_token: &Uuid,
-                   client_token: &Uuid) -> Result<(Uuid, yggdrasil::Profile), Error> {
-    let mut core = Core::new().unwrap();
-
-    let req = make_json_request(core.handle(), "https://authserver.mojang.com/refresh", json!({
-        "accessToken": access_token.simple().to_string(),
-        "clientToken": client_token.simple().to_string()
-    }));
-
-    core.run(req.map(|json| {
-        let error = || Error::UnrecognizedJson(json.to_string());
-        let uuid = Uuid::parse_str(json["selectedProfile"]["id"].as_str().ok_or(error())?).map_err(|_| error())?;
-        let name = json["selectedProfile"]["name"].as_str().ok_or(error())?.to_owned();
-        let properties = HashMap::new(); // TODO: deserialize properties
-        let access_token_string = json["accessToken"].as_str().ok_or(error())?;
-        let access_token = Uuid::parse_str(access_token_string).map_err(|_| error())?;
-        Result::Ok((access_token, yggdrasil::Profile::new(uuid, name, properties)))
-    }))?
+                        client_token: &Uuid) -> Result<(String, yggdrasil::Profile), Error> {
+    RequestClient::default().authenticate(&Default::default(), username, password, client_token)
+}
+
+pub fn req_refresh_async(handle: &Handle,
+                         mirror: &mirrors::MirrorConfig,
+                         access_token: &str,
+                         client_token: &Uuid) -> RequestFuture<(String, yggdrasil::Profile)> {
+    RequestClient::default().refresh_async(handle, mirror, access_token, client_token)
+}
+
+pub fn req_refresh(access_token: &str,
+                   client_token: &Uuid) -> Result<(String, yggdrasil::Profile), Error> {
+    RequestClient::default().refresh(&Default::default(), access_token, client_token)
+}
+
+pub fn req_validate_async(handle: &Handle,
+                          mirror: &mirrors::MirrorConfig,
+                          access_token: &str,
+                          client_token: &Uuid) -> RequestFuture<bool> {
+    RequestClient::default().validate_async(handle, mirror, access_token, client_token)
+}
+
+pub fn req_validate(access_token: &str,
+                    client_token: &Uuid) -> Result<bool, Error> {
+    RequestClient::default().validate(&Default::default(), access_token, client_token)
+}
+
+pub fn req_has_joined_async(handle: &Handle,
+                            mirror: &mirrors::MirrorConfig,
+                            username: &str,
+                            server_hash: &str) -> RequestFuture<Option<yggdrasil::Profile>> {
+    RequestClient::default().has_joined_async(handle, mirror, username, server_hash)
+}
+
+pub fn req_has_joined(username: &str, server_hash: &str) -> Result<Option<yggdrasil::Profile>, Error> {
+    RequestClient::default().has_joined(&Default::default(), username, server_hash)
+}
+
+pub fn req_versions_async(handle: &Handle, mirror: &mirrors::MirrorConfig) -> RequestFuture<serde_json::Value> {
+    RequestClient::default().versions_async(handle, mirror)
 }
 
 pub fn req_versions() -> Result<serde_json::Value, Error> {
-    let mut core = Core::new().unwrap();
-    let url = "https://launchermeta.mojang.com/mc/game/version_manifest.json";
+    req_versions_with_mirror(&Default::default())
+}
+
+pub fn req_versions_with_mirror(mirror: &mirrors::MirrorConfig) -> Result<serde_json::Value, Error> {
+    RequestClient::default().versions(mirror)
+}
+
+pub fn req_version_manifest_async(handle: &Handle,
+                                  mirror: &mirrors::MirrorConfig) -> RequestFuture<versions::VersionManifest> {
+    RequestClient::default().version_manifest_async(handle, mirror)
+}
+
+pub fn req_version_manifest_with_mirror(mirror: &mirrors::MirrorConfig) -> Result<versions::VersionManifest, Error> {
+    RequestClient::default().version_manifest(mirror)
+}
+
+pub fn req_latest_async(handle: &Handle,
+                        mirror: &mirrors::MirrorConfig,
+                        version_type: versions::VersionType) -> RequestFuture<versions::VersionManifestEntry> {
+    RequestClient::default().latest_async(handle, mirror, version_type)
+}
 
-    let req = make_json_request(core.handle(), url, serde_json::Value::Null);
+pub fn req_latest(version_type: versions::VersionType) -> Result<versions::VersionManifestEntry, Error> {
+    RequestClient::default().latest(&Default::default(), version_type)
+}
+
+pub fn req_version_json_async(handle: &Handle,
+                              mirror: &mirrors::MirrorConfig,
+                              url: &str) -> RequestFuture<serde_json::Value> {
+    RequestClient::default().version_json_async(handle, mirror, url)
+}
+
+pub fn req_version_json(url: &str) -> Result<serde_json::Value, Error> {
+    req_version_json_with_mirror(&Default::default(), url)
+}
+
+pub fn req_version_json_with_mirror(mirror: &mirrors::MirrorConfig, url: &str) -> Result<serde_json::Value, Error> {
+    RequestClient::default().version_json(mirror, url)
+}
 
-    core.run(req)
+pub fn req_deserialize_version_async(handle: &Handle,
+                                     mirror: &mirrors::MirrorConfig,
+                                     url: &str) -> RequestFuture<versions::MinecraftVersion> {
+    RequestClient::default().deserialize_version_async(handle, mirror, url)
 }
 
 pub fn req_deserialize_version(url: &str) -> Result<versions::MinecraftVersion, Error> {
-    let mut core = Core::new().unwrap();
+    req_deserialize_version_with_mirror(&Default::default(), url)
+}
 
-    let req = make_json_request(core.handle(), url, serde_json::Value::Null);
+pub fn req_deserialize_version_with_mirror(mirror: &mirrors::MirrorConfig,
+                                           url: &str) -> Result<versions::MinecraftVersion, Error> {
+    RequestClient::default().deserialize_version(mirror, url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::sync::mpsc::{self, Receiver};
+    use std::thread;
+
+    // answers one request per body with a plain 200 response
+    fn serve(bodies: Vec<&'static str>) -> String {
+        serve_responses(bodies.into_iter().map(|body| ("200 OK", "", body)).collect()).0
+    }
 
-    core.run(req.map(|json| {
-        Result::Ok(serde_json::from_value(json.clone()).unwrap())
-    }))?
+    // answers one request per (status, extra header lines, body) and hands back each request's head
+    fn serve_responses(responses: Vec<(&'static str, &'static str, &'static str)>) -> (String, Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for (status, headers, body) in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let (mut head, mut line) = (String::new(), String::new());
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    head.push_str(&line);
+                    line.clear()
+                }
+                let response = format!("HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                                       status, headers, body.len(), body);
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+                let _ = sender.send(head);
+            }
+        });
+        (format!("http://{}/", address), receiver)
+    }
+
+    #[test]
+    fn deserialize_malformed_version() {
+        let url = "https://example.com/1.12.2.json";
+        let version = deserialize_version(url, json!({ "id": "1.12.2", "type": "release", "time": "", "releaseTime": "" }));
+        assert_eq!(version.unwrap().id(), "1.12.2");
+        match deserialize_version(url, json!({ "id": "1.12.2", "type": "release", "libraries": {} })) {
+            Result::Err(Error::DeserializeError { url: ref error_url, .. }) => assert_eq!(error_url, url),
+            _ => panic!("expected a deserialize error")
+        }
+    }
+
+    #[test]
+    fn manifest_cached_after_parsing() {
+        let cache_dir = env::temp_dir().join(format!("rmcll-manifest-cache-{}", ::std::process::id()));
+        let _ = fs::remove_dir_all(cache_dir.as_path());
+        let mirror = mirrors::MirrorConfig::official().launcher_meta(&serve(vec![
+            "not a manifest",
+            r#"{ "latest": { "release": "1.12.2", "snapshot": "1.12.2" }, "versions": [] }"#,
+        ]));
+        let client = RequestClient::new();
+        assert!(client.version_manifest_cached(&mirror, cache_dir.as_path()).is_err());
+        assert!(!cache_dir.join(MANIFEST_CACHE_FILE).exists());
+        let manifest = client.version_manifest_cached(&mirror, cache_dir.as_path()).unwrap();
+        assert_eq!(manifest.latest_id(versions::VersionType::Release), Some("1.12.2"));
+        assert!(cache_dir.join(MANIFEST_CACHE_FILE).is_file());
+        assert!(!PartialFile::part_path_of(cache_dir.join(MANIFEST_CACHE_FILE).as_path()).exists());
+        fs::remove_dir_all(cache_dir).unwrap();
+    }
+
+    #[test]
+    fn manifest_revalidated_with_etag() {
+        let cache_dir = env::temp_dir().join(format!("rmcll-manifest-etag-{}", ::std::process::id()));
+        let _ = fs::remove_dir_all(cache_dir.as_path());
+        let (url, requests) = serve_responses(vec![
+            ("200 OK", "ETag: \"v1\"\r\nLast-Modified: Tue, 01 Jan 2019 00:00:00 GMT\r\n",
+             r#"{ "latest": { "release": "1.12.2", "snapshot": "1.12.2" }, "versions": [] }"#),
+            ("304 Not Modified", "ETag: \"v1\"\r\n", ""),
+        ]);
+        let mirror = mirrors::MirrorConfig::official().launcher_meta(&url);
+        let client = RequestClient::new();
+        client.version_manifest_cached(&mirror, cache_dir.as_path()).unwrap();
+        assert!(!requests.recv().unwrap().to_lowercase().contains("if-none-match"));
+        let manifest = client.version_manifest_cached(&mirror, cache_dir.as_path()).unwrap();
+        let head = requests.recv().unwrap().to_lowercase();
+        assert!(head.contains("if-none-match: \"v1\""));
+        assert!(head.contains("if-modified-since: tue, 01 jan 2019 00:00:00 gmt"));
+        assert_eq!(manifest.latest_id(versions::VersionType::Release), Some("1.12.2"));
+        fs::remove_dir_all(cache_dir).unwrap();
+    }
+
+    #[test]
+    fn download_resumed_with_range() {
+        let dir = env::temp_dir().join(format!("rmcll-download-resume-{}", ::std::process::id()));
+        let _ = fs::remove_dir_all(dir.as_path());
+        fs::create_dir_all(dir.as_path()).unwrap();
+        let path = dir.join("abc");
+        let part_path = PartialFile::part_path_of(path.as_path());
+        let sha1 = Some("a9993e364706816aba3e25717850c26c9cd0d89d");
+        let client = RequestClient::new();
+
+        // the server honours the range, so only the rest is appended and the whole file is hashed
+        fs::write(part_path.as_path(), b"ab").unwrap();
+        let (url, requests) = serve_responses(vec![("206 Partial Content", "Content-Range: bytes 2-2/3\r\n", "c")]);
+        client.download_to_file(&url, path.as_path(), sha1).unwrap();
+        assert!(requests.recv().unwrap().to_lowercase().contains("range: bytes=2-"));
+        assert_eq!(fs::read(path.as_path()).unwrap(), b"abc");
+        assert!(!part_path.exists());
+
+        // a server ignoring the range sends everything, which replaces the stale part
+        fs::remove_file(path.as_path()).unwrap();
+        fs::write(part_path.as_path(), b"xy").unwrap();
+        let (url, _) = serve_responses(vec![("200 OK", "", "abc")]);
+        client.download_to_file(&url, path.as_path(), sha1).unwrap();
+        assert_eq!(fs::read(path.as_path()).unwrap(), b"abc");
+
+        // a part longer than the file is refused with 416 and downloaded again from scratch
+        fs::remove_file(path.as_path()).unwrap();
+        fs::write(part_path.as_path(), b"abcd").unwrap();
+        let (url, requests) = serve_responses(vec![("416 Range Not Satisfiable", "", ""), ("200 OK", "", "abc")]);
+        client.download_to_file(&url, path.as_path(), sha1).unwrap();
+        assert!(requests.recv().unwrap().to_lowercase().contains("range: bytes=4-"));
+        assert!(!requests.recv().unwrap().to_lowercase().contains("range:"));
+        assert_eq!(fs::read(path.as_path()).unwrap(), b"abc");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn custom_connector_per_core() {
+        let dir = env::temp_dir().join(format!("rmcll-custom-connector-{}", ::std::process::id()));
+        let _ = fs::remove_dir_all(dir.as_path());
+        let built = Rc::new(::std::cell::Cell::new(0));
+        let counter = built.clone();
+        let client = RequestClient::new().with_connector(move |handle| {
+            counter.set(counter.get() + 1);
+            default_connector(handle)
+        });
+        let task = DownloadTask::new(format!("{}abc", serve(vec!["abc"])), dir.join("abc"),
+                                     Some("a9993e364706816aba3e25717850c26c9cd0d89d".to_owned()));
+        client.download_all(&[task], None, None).unwrap();
+        assert_eq!(built.get(), 1);
+        assert_eq!(fs::read(dir.join("abc")).unwrap(), b"abc");
+        fs::remove_dir_all(dir).unwrap();
+
+        let core = Core::new().unwrap();
+        let prebuilt = Client::configure().connector(default_connector(&core.handle())).build(&core.handle());
+        let client = RequestClient::new().with_client(prebuilt, &core.handle());
+        match client.version_json(&Default::default(), "http://127.0.0.1:1/") {
+            Result::Err(Error::ForeignCore) => (),
+            _ => panic!("expected a foreign core error")
+        }
+    }
 }
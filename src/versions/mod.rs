@@ -3,25 +3,43 @@
 
 use std::io;
 use std::fs;
+use std::env;
 use std::fmt;
 use std::error;
+use std::cmp;
+use std::thread;
 use std::rc::Rc;
+use std::cell::RefCell;
+use std::sync::{mpsc, Arc, Mutex};
 use std::ffi::OsString;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::result::Result;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use zip::read::ZipArchive;
 use zip::result::ZipError;
 use serde_json::{Value, self};
+use serde::ser::{Serialize, Serializer};
 use serde::de::{Deserialize, Deserializer, Visitor, MapAccess, self};
 
+use checksum::{self, Checksum, HashAlgo};
 use launcher;
+use mirrors;
 use parsing;
+use requests;
+use progress::{self, ProgressEvent, ProgressListener};
 
 #[cfg(target_pointer_width = "32")]
 const OS_ARCH: &str = "32";
 #[cfg(target_pointer_width = "64")]
 const OS_ARCH: &str = "64";
+#[cfg(target_arch = "x86")]
+const OS_ARCH_NAME: &str = "x86";
+#[cfg(target_arch = "x86_64")]
+const OS_ARCH_NAME: &str = "x86_64";
+#[cfg(target_arch = "aarch64")]
+const OS_ARCH_NAME: &str = "arm64";
+#[cfg(target_arch = "arm")]
+const OS_ARCH_NAME: &str = "arm32";
 #[cfg(target_os = "windows")]
 const OS_PLATFORM: &str = "windows";
 #[cfg(target_os = "macos")]
@@ -29,9 +47,20 @@ const OS_PLATFORM: &str = "macos";
 #[cfg(target_os = "linux")]
 const OS_PLATFORM: &str = "linux";
 
-const CLASSPATH_SEPARATOR: &str = ":";
+const EXTRACT_CONCURRENCY: usize = 4;
 
-#[derive(Deserialize, Debug)]
+const BUNDLER_MAIN_CLASS: &str = "net.minecraft.bundler.Main";
+
+lazy_static! {
+    static ref EXTRACT_LOCKS: Mutex<HashMap<PathBuf, Arc<Mutex<()>>>> = Mutex::new(HashMap::new());
+}
+
+#[cfg(target_os = "windows")]
+pub const CLASSPATH_SEPARATOR: &str = ";";
+#[cfg(not(target_os = "windows"))]
+pub const CLASSPATH_SEPARATOR: &str = ":";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct MinecraftVersion {
     id: String,
     #[serde(rename = "type")]
@@ -40,36 +69,101 @@ pub struct MinecraftVersion {
     publish_time: String,
     #[serde(rename = "releaseTime")]
     release_time: String,
-    // TODO: 1.13+ arguments
-    /*
-    #[serde(default)]
-    arguments: HashMap<String, String>,
-    */
-    #[serde(rename = "minecraftArguments")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    arguments: Option<Arguments>,
+    #[serde(rename = "minecraftArguments", skip_serializing_if = "Option::is_none")]
     minecraft_arguments: Option<String>,
-    #[serde(rename = "mainClass", default)]
+    #[serde(rename = "mainClass", default, skip_serializing_if = "Option::is_none")]
     main_class: Option<String>,
-    #[serde(rename = "jar", default)]
+    #[serde(rename = "jar", default, skip_serializing_if = "Option::is_none")]
     version_jar: Option<String>,
-    #[serde(rename = "assets")]
+    #[serde(rename = "assets", skip_serializing_if = "Option::is_none")]
     assets_id: Option<String>,
-    #[serde(rename = "assetIndex")]
+    #[serde(rename = "assetIndex", skip_serializing_if = "Option::is_none")]
     asset_index: Option<AssetDownloadInfo>,
-    #[serde(default)]
+    #[serde(default, skip_serializing)]
     assets: Option<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     libraries: Vec<Library>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     downloads: HashMap<String, DownloadInfo>,
-    #[serde(rename = "inheritsFrom")]
+    #[serde(rename = "inheritsFrom", skip_serializing_if = "Option::is_none")]
     inherits_from: Option<String>,
+    #[serde(rename = "javaVersion", skip_serializing_if = "Option::is_none")]
+    java_version: Option<JavaVersionInfo>,
+    #[serde(rename = "clientVersion", skip_serializing_if = "Option::is_none")]
+    client_version: Option<String>,
+    #[serde(rename = "complianceLevel", skip_serializing_if = "Option::is_none")]
+    compliance_level: Option<u32>,
+    #[serde(rename = "minimumLauncherVersion", skip_serializing_if = "Option::is_none")]
+    minimum_launcher_version: Option<u32>,
+    #[serde(flatten)]
+    extra: HashMap<String, Value>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JavaVersionInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    component: Option<String>,
+    #[serde(rename = "majorVersion")]
+    major_version: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Arguments {
+    #[serde(default)]
+    game: Vec<Argument>,
+    #[serde(default)]
+    jvm: Vec<Argument>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum Argument {
+    Plain(String),
+    Conditional { rules: Vec<Rule>, value: ArgumentValue },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum ArgumentValue {
+    Single(String),
+    Multiple(Vec<String>),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Rule {
+    action: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    os: Option<OsRule>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    features: HashMap<String, bool>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OsRule {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    arch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    version: Option<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct FeatureSet(HashMap<String, bool>);
+
 #[derive(Debug)]
 pub struct DownloadStrategy {
     with_classifier: HashMap<String, (String, DownloadInfo)>,
     default: Option<DownloadInfo>,
-    rules: Vec<(String, String)>,
+    rules: Vec<(String, String, String)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RuleDecision {
+    Allowed(Option<String>),
+    Denied(Option<String>),
 }
 
 #[derive(Clone, Debug)]
@@ -78,41 +172,147 @@ pub struct Library {
     is_native: bool,
     downloads: Rc<DownloadStrategy>,
     extract_ignored: Rc<Vec<String>>,
+    raw: Rc<Value>,
 }
 
 #[derive(Clone, Debug)]
 pub struct NativeCollection {
-    libraries: Vec<(PathBuf, Rc<Vec<String>>)>
+    libraries: Vec<(PathBuf, Rc<Vec<String>>, bool)>,
+    extra_extract_ignored: Vec<String>,
 }
 
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct NativeJar {
+    path: PathBuf,
+    #[serde(default)]
+    exclude: Vec<String>,
+    #[serde(default)]
+    flatten: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(untagged)]
 pub enum DownloadInfo {
-    PreHashed { size: i32, url: String, sha1: String },
+    PreHashed { size: i32, url: String, #[serde(flatten)] checksum: Checksum },
     RawXzip { url: String },
     Raw { url: String },
 }
 
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct AssetDownloadInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
     size: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     sha1: Option<String>,
     #[serde(rename = "id")]
     asset_index_id: String,
-    #[serde(rename = "totalSize")]
+    #[serde(rename = "totalSize", skip_serializing_if = "Option::is_none")]
     total_size: Option<i64>,
-    #[serde(rename = "known", default)]
+    #[serde(rename = "known", default, skip_serializing_if = "is_false")]
     size_and_hash_known: bool,
 }
 
-pub struct VersionManager(Box<Path>);
+#[derive(Deserialize, Clone, Debug)]
+pub struct AssetObject {
+    hash: String,
+    size: i64,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct AssetIndex {
+    #[serde(default)]
+    objects: HashMap<String, AssetObject>,
+    #[serde(rename = "virtual", default)]
+    is_virtual: bool,
+    #[serde(default)]
+    map_to_resources: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VerifyStatus {
+    Ok,
+    Mismatch,
+    Missing,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VersionType {
+    #[serde(rename = "release")]
+    Release,
+    #[serde(rename = "snapshot")]
+    Snapshot,
+    #[serde(rename = "old_beta")]
+    OldBeta,
+    #[serde(rename = "old_alpha")]
+    OldAlpha,
+    // types added to the manifest later (e.g. "pending") must not break the whole list
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct LatestVersions {
+    release: String,
+    snapshot: String,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct VersionManifestEntry {
+    id: String,
+    #[serde(rename = "type")]
+    version_type: VersionType,
+    url: String,
+    #[serde(rename = "time")]
+    publish_time: String,
+    #[serde(rename = "releaseTime")]
+    release_time: String,
+    #[serde(default)]
+    sha1: Option<String>,
+    #[serde(rename = "complianceLevel", default)]
+    compliance_level: Option<u32>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct VersionManifest {
+    latest: LatestVersions,
+    versions: Vec<VersionManifestEntry>,
+}
+
+pub struct VersionManager(Box<Path>, RefCell<HashMap<String, Rc<MinecraftVersion>>>, Option<Box<dyn VersionMetadataSource>>, requests::RequestClient);
+
+pub trait VersionMetadataSource {
+    fn version_json(&self, id: &str) -> Result<Value, Error>;
+
+    // sources that can reach the original bytes should store them as is
+    fn write_version_json(&self, id: &str, path: &Path) -> Result<(), Error> {
+        let json = self.version_json(id)?;
+        fs::write(path, serde_json::to_vec(&json)?)?;
+        Result::Ok(())
+    }
+}
+
+pub struct LocalVersionSource(PathBuf);
+
+pub struct MojangVersionSource(mirrors::MirrorConfig, requests::RequestClient);
+
+#[derive(Deserialize)]
+struct VersionParent {
+    #[serde(rename = "inheritsFrom")]
+    inherits_from: Option<String>,
+}
 
 #[derive(Debug)]
 pub enum Error {
     FileUnavailableError(Box<Path>),
+    UnknownVersion(String),
+    InvalidVersionId(String),
+    SizeMismatch { path: Box<Path>, expected: i64, actual: i64 },
+    UnrecognizedHash(String),
     UnrecognizedPathString(OsString),
-    IOError(Box<error::Error + Send + Sync>),
+    ArgumentParseError(parsing::ParseError),
+    IOError(Box<dyn error::Error + Send + Sync>),
 }
 
 impl From<serde_json::Error> for Error {
@@ -133,6 +333,18 @@ impl From<io::Error> for Error {
     }
 }
 
+impl From<requests::Error> for Error {
+    fn from(e: requests::Error) -> Self {
+        Error::IOError(Box::new(e))
+    }
+}
+
+impl From<parsing::ParseError> for Error {
+    fn from(e: parsing::ParseError) -> Self {
+        Error::ArgumentParseError(e)
+    }
+}
+
 impl From<ZipError> for Error {
     fn from(e: ZipError) -> Self {
         Error::IOError(Box::new(io::Error::from(e)))
@@ -140,73 +352,1076 @@ impl From<ZipError> for Error {
 }
 
 impl NativeCollection {
-    fn is_file_included(&self, extract_ignored: &Vec<String>, file_name: &str) -> bool {
-        extract_ignored.iter().find(|rule| file_name.starts_with(rule.as_str())).is_none()
+    pub fn from_jars(jars: Vec<NativeJar>) -> NativeCollection {
+        let libraries = jars.into_iter().map(|jar| (jar.path, Rc::new(jar.exclude), jar.flatten)).collect();
+        NativeCollection { libraries, extra_extract_ignored: Vec::new() }
+    }
+
+    pub fn jars(&self) -> Vec<NativeJar> {
+        self.libraries.iter().map(|&(ref path, ref extract_ignored, flatten)| {
+            let exclude = extract_ignored.iter().chain(self.extra_extract_ignored.iter()).cloned().collect();
+            NativeJar { path: path.clone(), exclude, flatten }
+        }).collect()
+    }
+
+    pub fn source_jars(&self) -> Vec<&Path> {
+        self.libraries.iter().map(|(path, _, _)| path.as_path()).collect()
+    }
+
+    fn is_file_included(&self, extract_ignored: &[String], file_name: &str) -> bool {
+        extract_ignored.iter().chain(self.extra_extract_ignored.iter()).find(|rule| {
+            file_name.starts_with(rule.as_str())
+        }).is_none()
     }
 
     pub fn extract_to(&self, target_dir_path: &Path) -> Result<Vec<String>, Error> {
-        let mut result = Vec::new();
-        let target_path_buf = target_dir_path.to_path_buf();
+        self.extract_to_with_progress(target_dir_path, None)
+    }
+
+    pub fn extract_to_with_progress(&self,
+                                    target_dir_path: &Path,
+                                    progress: Option<&mut dyn ProgressListener>) -> Result<Vec<String>, Error> {
+        let mut progress = progress;
         if !target_dir_path.is_dir() { fs::create_dir_all(target_dir_path)? }
-        for &(ref path_buf, ref extract_ignored) in self.libraries.iter() {
-            let zip_file = fs::File::open(path_buf)?;
-            let mut zip = ZipArchive::new(zip_file)?;
+        // launches sharing a natives directory within this process extract one at a time,
+        // while other directories proceed in parallel; separate processes are not coordinated
+        let lock = ExtractLock::acquire(fs::canonicalize(target_dir_path)?);
+        let _guard = lock.1.lock().unwrap_or_else(|e| e.into_inner());
+        // the last jar providing a target file wins, so identically named entries never race
+        let mut owners: HashMap<String, usize> = HashMap::new();
+        let mut jobs = Vec::new();
+        for (index, &(ref path_buf, ref extract_ignored, flatten)) in self.libraries.iter().enumerate() {
+            let mut zip = ZipArchive::new(fs::File::open(path_buf)?)?;
+            let mut entries = Vec::new();
             for i in 0..zip.len() {
-                let mut source = zip.by_index(i)?;
-                let file_name = source.name().to_owned();
-                if self.is_file_included(&extract_ignored, file_name.as_str()) {
-                    let target_path = target_path_buf.join(file_name.as_str());
-                    let mut target = fs::File::create(target_path)?;
-                    io::copy(&mut source, &mut target)?;
-                    result.push(file_name);
+                let file_name = zip.by_index(i)?.name().to_owned();
+                if file_name.ends_with('/') || !self.is_file_included(extract_ignored, file_name.as_str()) { continue; }
+                let target_name = if flatten { file_name.rsplit('/').next().unwrap_or("").to_owned() } else { file_name.clone() };
+                owners.insert(target_name.clone(), index);
+                entries.push((i, file_name, target_name));
+            }
+            jobs.push((index, path_buf.clone(), entries));
+        }
+        for &mut (index, _, ref mut entries) in jobs.iter_mut() {
+            entries.retain(|(_, _, target_name)| owners.get(target_name) == Some(&index));
+        }
+        let worker_count = cmp::min(EXTRACT_CONCURRENCY, jobs.len());
+        let mut results: Vec<Option<Result<Vec<String>, Error>>> = (0..jobs.len()).map(|_| None).collect();
+        let queue = Arc::new(Mutex::new(jobs));
+        let (sender, receiver) = mpsc::channel();
+        let workers: Vec<_> = (0..worker_count).map(|_| {
+            let (queue, sender, target_dir) = (queue.clone(), sender.clone(), target_dir_path.to_path_buf());
+            thread::spawn(move || loop {
+                let job = queue.lock().unwrap().pop();
+                match job {
+                    Some((index, path_buf, entries)) => {
+                        let result = extract_entries(path_buf.as_path(), &entries, target_dir.as_path(), &sender);
+                        let _ = sender.send(ExtractMessage::Finished(index, result));
+                    }
+                    None => break
                 }
+            })
+        }).collect();
+        drop(sender);
+        for message in receiver {
+            match message {
+                ExtractMessage::Entry(name) => progress::notify(&mut progress, ProgressEvent::ExtractEntry { name }),
+                ExtractMessage::Finished(index, result) => results[index] = Some(result),
             }
         }
-        Result::Ok(result)
+        for worker in workers { let _ = worker.join(); }
+        let mut extracted = Vec::new();
+        for result in results {
+            match result {
+                Some(result) => extracted.extend(result?),
+                None => return Result::Err(Error::IOError(Box::from("native extraction worker panicked")))
+            }
+        }
+        Result::Ok(extracted)
+    }
+}
+
+struct ExtractLock(PathBuf, Arc<Mutex<()>>);
+
+impl ExtractLock {
+    fn acquire(target_dir_path: PathBuf) -> ExtractLock {
+        let mut locks = EXTRACT_LOCKS.lock().unwrap_or_else(|e| e.into_inner());
+        let lock = locks.entry(target_dir_path.clone()).or_insert_with(|| Arc::new(Mutex::new(()))).clone();
+        ExtractLock(target_dir_path, lock)
+    }
+}
+
+impl Drop for ExtractLock {
+    fn drop(&mut self) {
+        // the map holds one reference and this lock the other, so nobody else is waiting for the directory
+        let mut locks = EXTRACT_LOCKS.lock().unwrap_or_else(|e| e.into_inner());
+        if Arc::strong_count(&self.1) == 2 { locks.remove(&self.0); }
+    }
+}
+
+enum ExtractMessage {
+    Entry(String),
+    Finished(usize, Result<Vec<String>, Error>),
+}
+
+fn extract_entries(path: &Path,
+                   entries: &[(usize, String, String)],
+                   target_dir_path: &Path,
+                   sender: &mpsc::Sender<ExtractMessage>) -> Result<Vec<String>, Error> {
+    let mut zip = ZipArchive::new(fs::File::open(path)?)?;
+    let mut result = Vec::new();
+    for &(i, ref file_name, ref target_name) in entries.iter() {
+        let mut source = zip.by_index(i)?;
+        let mut target = fs::File::create(target_dir_path.join(target_name))?;
+        io::copy(&mut source, &mut target)?;
+        let _ = sender.send(ExtractMessage::Entry(file_name.clone()));
+        result.push(file_name.clone());
     }
+    Result::Ok(result)
 }
 
 impl VersionManager {
     pub fn new(path: &Path) -> VersionManager {
-        VersionManager(Box::from(path))
+        VersionManager(Box::from(path), RefCell::new(HashMap::new()), None, requests::RequestClient::default())
+    }
+
+    pub fn with_absolute_root(path: &Path) -> Result<VersionManager, Error> {
+        let path_buf = if path.exists() {
+            fs::canonicalize(path)?
+        } else if path.is_relative() {
+            env::current_dir()?.join(path)
+        } else {
+            path.to_path_buf()
+        };
+        Result::Ok(VersionManager(path_buf.into_boxed_path(), RefCell::new(HashMap::new()), None, requests::RequestClient::default()))
+    }
+
+    // only install_from_source consults it, reading a version never leaves the disk
+    pub fn with_source(mut self, source: Box<dyn VersionMetadataSource>) -> Self {
+        self.2 = Some(source);
+        self
+    }
+
+    // used for every download the manager performs
+    pub fn with_request_client(mut self, client: requests::RequestClient) -> Self {
+        self.3 = client;
+        self
+    }
+
+    pub fn request_client(&self) -> &requests::RequestClient {
+        &self.3
     }
 
     pub fn get_version_path(&self) -> PathBuf {
         self.0.to_path_buf()
     }
 
-    pub fn get_natives_path(&self, id: &str) -> PathBuf {
+    pub fn get_natives_path(&self, id: &str) -> Result<PathBuf, Error> {
+        check_version_id(id)?;
         let sub_path = format!("{}-natives-{}-{}/", id, OS_PLATFORM, OS_ARCH);
         let mut path_buf = self.0.join(id);
         path_buf.push(sub_path);
-        path_buf
+        Result::Ok(path_buf)
+    }
+
+    pub fn get_primary_jar_path(&self, id: &str) -> Result<PathBuf, Error> {
+        check_version_id(id)?;
+        Result::Ok(self.0.join(format!("{0}/{0}.jar", id)))
+    }
+
+    pub fn install_version(&self,
+                           id: &str,
+                           mirror: &mirrors::MirrorConfig,
+                           progress: Option<&mut dyn ProgressListener>,
+                           cancel: Option<&requests::CancelToken>) -> Result<MinecraftVersion, Error> {
+        check_version_id(id)?;
+        let manifest = self.3.version_manifest(mirror)?;
+        let entry = manifest.get(id).ok_or_else(|| Error::UnknownVersion(id.to_owned()))?;
+        let sha1 = entry.sha1().map(String::from);
+        let path_buf_json = self.0.join(format!("{0}/{0}.json", id));
+        self.1.borrow_mut().remove(id);
+        self.3.download_all(&[requests::DownloadTask::new(mirror.rewrite(entry.url()), path_buf_json, sha1)], progress, cancel)?;
+        self.version_of(id)
+    }
+
+    pub fn install_version_with_client_jar(&self,
+                                           id: &str,
+                                           mirror: &mirrors::MirrorConfig,
+                                           progress: Option<&mut dyn ProgressListener>,
+                                           cancel: Option<&requests::CancelToken>) -> Result<(MinecraftVersion, PathBuf), Error> {
+        let mut progress = progress;
+        let version = self.install_version(id, mirror, progress::reborrow(&mut progress), cancel)?;
+        let jar_path = self.get_primary_jar_path(id)?;
+        let task = match version.primary_jar_download(self) {
+            Some(info) => requests::DownloadTask::with_checksum(mirror.rewrite(info.url()), jar_path.clone(), info.checksum().cloned()),
+            None => return Result::Err(Error::FileUnavailableError(jar_path.into_boxed_path()))
+        };
+        self.3.download_all(&[task], progress, cancel)?;
+        Result::Ok((version, jar_path))
+    }
+
+    pub fn remove_version(&self, id: &str) -> Result<(), Error> {
+        check_version_id(id)?;
+        let path_buf = self.0.join(id);
+        if !path_buf.is_dir() { return Result::Err(Error::UnknownVersion(id.to_owned())); }
+        // refuse to follow a symlink or junction out of the managed root
+        if fs::canonicalize(path_buf.as_path())?.parent() != Some(fs::canonicalize(&self.0)?.as_path()) {
+            return Result::Err(Error::UnknownVersion(id.to_owned()));
+        }
+        self.1.borrow_mut().remove(id);
+        fs::remove_dir_all(path_buf)?;
+        Result::Ok(())
+    }
+
+    pub fn download_assets(&self,
+                           assets_dir: &Path,
+                           index: &AssetIndex,
+                           mirror: &mirrors::MirrorConfig,
+                           progress: Option<&mut dyn ProgressListener>,
+                           cancel: Option<&requests::CancelToken>) -> Result<(), Error> {
+        let missing = missing_asset_objects(index, assets_dir)?;
+        self.download_asset_objects(assets_dir, &missing, mirror, progress, cancel)
+    }
+
+    pub fn download_asset_objects(&self,
+                                  assets_dir: &Path,
+                                  objects: &[AssetObject],
+                                  mirror: &mirrors::MirrorConfig,
+                                  progress: Option<&mut dyn ProgressListener>,
+                                  cancel: Option<&requests::CancelToken>) -> Result<(), Error> {
+        let mut tasks: Vec<requests::DownloadTask> = Vec::new();
+        for object in objects {
+            let relative_path = object.relative_path().ok_or_else(|| Error::UnrecognizedHash(object.hash.clone()))?;
+            let path_buf = assets_dir.join("objects").join(&relative_path);
+            let url = mirror.resources_url(&relative_path);
+            tasks.push(requests::DownloadTask::new(url, path_buf, Some(object.hash.clone())));
+        }
+        self.3.download_all(&tasks, progress, cancel)?;
+        Result::Ok(())
     }
 
     pub fn extract_natives(&self, id: &str, library_path: &Path) -> Result<Vec<String>, Error> {
         let info = self.version_of(id)?;
-        let path_buf = self.get_natives_path(id);
+        let path_buf = self.get_natives_path(id)?;
         info.to_native_collection(self, library_path)?.extract_to(path_buf.as_path())
     }
 
+    pub fn prune_natives(&self, id: &str) -> Result<Vec<PathBuf>, Error> {
+        check_version_id(id)?;
+        let current = format!("{}-natives-{}-{}", id, OS_PLATFORM, OS_ARCH);
+        let prefix = format!("{}-natives-", id);
+        let mut removed = Vec::new();
+        let version_path = self.0.join(id);
+        if !version_path.is_dir() { return Result::Ok(removed); }
+        for entry in fs::read_dir(version_path.as_path())? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.starts_with(&prefix) || name == current || name.ends_with(".pruning") { continue; }
+            if !entry.file_type()?.is_dir() { continue; }
+            // a directory whose libraries are loaded by a running game cannot be renamed on windows,
+            // so renaming first keeps an in-use directory from being left half deleted
+            let path_buf = entry.path();
+            let pruning_path = version_path.join(format!("{}.pruning", name));
+            if fs::rename(path_buf.as_path(), pruning_path.as_path()).is_err() { continue; }
+            fs::remove_dir_all(pruning_path)?;
+            removed.push(path_buf);
+        }
+        Result::Ok(removed)
+    }
+
+    pub fn prune_all_natives(&self) -> Result<Vec<PathBuf>, Error> {
+        let mut removed = Vec::new();
+        for entry in fs::read_dir(&self.0)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() { continue; }
+            if let Some(id) = entry.file_name().to_str() {
+                if check_version_id(id).is_ok() { removed.extend(self.prune_natives(id)?); }
+            }
+        }
+        Result::Ok(removed)
+    }
+
+    // stores the json of the version and of every version it inherits from when missing on disk
+    pub fn install_from_source(&self, id: &str) -> Result<MinecraftVersion, Error> {
+        let mut next = Some(id.to_owned());
+        while let Some(id) = next {
+            check_version_id(&id)?;
+            let path_buf = self.0.join(format!("{0}/{0}.json", id));
+            if !path_buf.is_file() {
+                let source = self.2.as_ref().ok_or_else(|| Error::FileUnavailableError(path_buf.clone().into_boxed_path()))?;
+                fs::create_dir_all(self.0.join(&id))?;
+                self.1.borrow_mut().remove(&id);
+                source.write_version_json(&id, path_buf.as_path())?;
+            }
+            next = self.inherits_from(&id)?;
+        }
+        self.version_of(id)
+    }
+
+    pub fn version_json_raw(&self, id: &str) -> Result<Value, Error> {
+        check_version_id(id)?;
+        let path_buf = self.0.join(format!("{0}/{0}.json", id));
+        if !path_buf.is_file() { return Result::Err(Error::FileUnavailableError(path_buf.into_boxed_path())); }
+        Result::Ok(serde_json::from_reader(fs::File::open(path_buf)?)?)
+    }
+
+    pub fn inherits_from(&self, id: &str) -> Result<Option<String>, Error> {
+        check_version_id(id)?;
+        if let Some(version) = self.1.borrow().get(id) { return Result::Ok(version.inherits_from.clone()); }
+        let path_buf = self.0.join(format!("{0}/{0}.json", id));
+        if !path_buf.is_file() { return Result::Err(Error::FileUnavailableError(path_buf.into_boxed_path())); }
+        let parent: VersionParent = serde_json::from_reader(io::BufReader::new(fs::File::open(path_buf)?))?;
+        Result::Ok(parent.inherits_from)
+    }
+
+    pub fn is_installed(&self, id: &str) -> bool {
+        if check_version_id(id).is_err() || !self.0.join(format!("{0}/{0}.json", id)).is_file() { return false; }
+        self.version_of(id).and_then(|version| version.version_jar_path(self)).map(|jar| jar.is_file()).unwrap_or(false)
+    }
+
+    pub fn needs_repair(&self, id: &str, library_path: &Path, assets_dir: &Path) -> Result<bool, Error> {
+        if !self.is_installed(id) { return Result::Ok(true); }
+        let version = self.version_of(id)?;
+        if !version.verify_libraries(self, library_path)?.is_empty() { return Result::Ok(true); }
+        let jar_checksum = version.primary_jar_download(self).and_then(|info| info.checksum().cloned());
+        if verify_file(version.version_jar_path(self)?.as_path(), jar_checksum.as_ref())? != VerifyStatus::Ok {
+            return Result::Ok(true);
+        }
+        if let Some(asset_index) = version.asset_index(self) {
+            let index_path = assets_dir.join(format!("indexes/{}.json", asset_index.id()));
+            if !index_path.is_file() { return Result::Ok(true); }
+            let index = AssetIndex::from_json_file(index_path.as_path())?;
+            if !missing_asset_objects(&index, assets_dir)?.is_empty() { return Result::Ok(true); }
+        }
+        Result::Ok(false)
+    }
+
     pub fn version_of(&self, id: &str) -> Result<MinecraftVersion, Error> {
+        check_version_id(id)?;
+        if let Some(version) = self.1.borrow().get(id) { return Result::Ok(version.as_ref().clone()); }
         let path_buf = self.0.join(id);
         if !path_buf.is_dir() { fs::create_dir_all(path_buf.as_path())? }
-        let path_buf_json = path_buf.join(format!("{}.json", id));
-        if path_buf_json.exists() {
-            Result::Ok(serde_json::from_reader(fs::File::open(path_buf_json)?)?)
-        } else {
-            Result::Err(Error::FileUnavailableError(path_buf_json.into_boxed_path()))
-        }
+        let version = MinecraftVersion::from_json_file(self.0.join(format!("{0}/{0}.json", id)).as_path())?;
+        self.1.borrow_mut().insert(id.to_owned(), Rc::new(version.clone()));
+        Result::Ok(version)
+    }
+
+    pub fn clear_cache(&self) {
+        self.1.borrow_mut().clear()
+    }
+}
+
+impl LocalVersionSource {
+    pub fn new(path: &Path) -> LocalVersionSource {
+        LocalVersionSource(path.to_path_buf())
+    }
+}
+
+impl VersionMetadataSource for LocalVersionSource {
+    fn version_json(&self, id: &str) -> Result<Value, Error> {
+        check_version_id(id)?;
+        let path_buf = self.0.join(format!("{0}/{0}.json", id));
+        if !path_buf.is_file() { return Result::Err(Error::FileUnavailableError(path_buf.into_boxed_path())); }
+        Result::Ok(serde_json::from_reader(io::BufReader::new(fs::File::open(path_buf)?))?)
+    }
+
+    fn write_version_json(&self, id: &str, path: &Path) -> Result<(), Error> {
+        check_version_id(id)?;
+        let path_buf = self.0.join(format!("{0}/{0}.json", id));
+        if !path_buf.is_file() { return Result::Err(Error::FileUnavailableError(path_buf.into_boxed_path())); }
+        fs::copy(path_buf, path)?;
+        Result::Ok(())
+    }
+}
+
+impl MojangVersionSource {
+    pub fn new(mirror: mirrors::MirrorConfig) -> MojangVersionSource {
+        MojangVersionSource(mirror, requests::RequestClient::default())
+    }
+
+    pub fn with_request_client(mut self, client: requests::RequestClient) -> Self {
+        self.1 = client;
+        self
+    }
+}
+
+impl Default for MojangVersionSource {
+    fn default() -> Self {
+        MojangVersionSource::new(Default::default())
+    }
+}
+
+impl VersionMetadataSource for MojangVersionSource {
+    fn version_json(&self, id: &str) -> Result<Value, Error> {
+        let manifest = self.1.version_manifest(&self.0)?;
+        let entry = manifest.get(id).ok_or_else(|| Error::UnknownVersion(id.to_owned()))?;
+        Result::Ok(self.1.version_json(&self.0, entry.url())?)
+    }
+
+    fn write_version_json(&self, id: &str, path: &Path) -> Result<(), Error> {
+        let manifest = self.1.version_manifest(&self.0)?;
+        let entry = manifest.get(id).ok_or_else(|| Error::UnknownVersion(id.to_owned()))?;
+        Result::Ok(self.1.download_to_file(&self.0.rewrite(entry.url()), path, entry.sha1())?)
     }
 }
 
 impl MinecraftVersion {
+    pub fn from_json_file(path: &Path) -> Result<MinecraftVersion, Error> {
+        if path.exists() {
+            MinecraftVersion::from_reader(fs::File::open(path)?)
+        } else {
+            Result::Err(Error::FileUnavailableError(Box::from(path)))
+        }
+    }
+
+    pub fn from_reader<R: io::Read>(reader: R) -> Result<MinecraftVersion, Error> {
+        Result::Ok(serde_json::from_reader(reader)?)
+    }
+
     pub fn id(&self) -> &str {
         &self.id
     }
 
-    pub fn version_type(&self) -> &str {
-        &self.version_type
+    pub fn version_type(&self) -> &str {
+        &self.version_type
+    }
+
+    pub fn publish_time(&self) -> &str {
+        &self.publish_time
+    }
+
+    pub fn release_time(&self) -> &str {
+        &self.release_time
+    }
+
+    pub fn inherits_from(&self) -> Option<&str> {
+        self.inherits_from.as_deref()
+    }
+
+    pub fn client_version(&self) -> Option<&str> {
+        self.client_version.as_deref()
+    }
+
+    pub fn compliance_level(&self) -> Option<u32> {
+        self.compliance_level
+    }
+
+    pub fn minimum_launcher_version(&self) -> Option<u32> {
+        self.minimum_launcher_version
+    }
+
+    pub fn uses_modern_arguments(&self) -> bool {
+        self.arguments.is_some()
+    }
+
+    pub fn requires_modern_launcher(&self) -> bool {
+        self.compliance_level.is_some_and(|level| level >= 1)
+    }
+
+    pub fn asset_index(&self, manager: &VersionManager) -> Option<AssetDownloadInfo> {
+        if let Some(ref asset_index) = self.asset_index { return Some(asset_index.clone()); }
+        let inherited = if let Some(ref inherits_from) = self.inherits_from {
+            manager.version_of(inherits_from).ok().and_then(|v| v.asset_index(manager))
+        } else {
+            None
+        };
+        match (self.assets_id.clone(), inherited) {
+            (Some(id), Some(ref info)) if info.id() != id => Some(AssetDownloadInfo::new(id)),
+            (Some(id), None) => Some(AssetDownloadInfo::new(id)),
+            (_, inherited) => inherited
+        }
+    }
+
+    pub fn assets_index_id(&self, manager: &VersionManager) -> Option<String> {
+        self.asset_index(manager).map(|asset_index| asset_index.id().to_owned())
+    }
+
+    pub fn download_asset_index(&self,
+                                manager: &VersionManager,
+                                assets_dir: &Path,
+                                mirror: &mirrors::MirrorConfig,
+                                progress: Option<&mut dyn ProgressListener>) -> Result<PathBuf, Error> {
+        let asset_index = self.asset_index(manager).ok_or_else(|| Error::UnknownVersion(self.id.clone()))?;
+        let path_buf = assets_dir.join(format!("indexes/{}.json", asset_index.id()));
+        // mojang's own version json never sets "known", so any listed size and sha1 are checked
+        let expected_size = asset_index.size();
+        let checksum = asset_index.sha1().map(|sha1| Checksum::sha1(sha1.to_owned()));
+        let info = DownloadInfo::from(asset_index);
+        let task = requests::DownloadTask::with_checksum(mirror.rewrite(info.url()), path_buf.clone(), checksum);
+        manager.3.download_all(&[task], progress, None)?;
+        if let Some(expected) = expected_size {
+            let actual = fs::metadata(path_buf.as_path())?.len() as i64;
+            if actual != expected {
+                fs::remove_file(path_buf.as_path())?;
+                return Result::Err(Error::SizeMismatch { path: path_buf.into_boxed_path(), expected, actual });
+            }
+        }
+        Result::Ok(path_buf)
+    }
+
+    pub fn asset_objects_missing(&self, index: &AssetIndex, assets_dir: &Path) -> Result<Vec<AssetObject>, Error> {
+        missing_asset_objects(index, assets_dir)
+    }
+
+    // the directory legacy versions expect as ${game_assets}, or none for the hashed object layout
+    pub fn legacy_assets_dir(&self, manager: &VersionManager, assets_dir: &Path, game_dir: &Path) -> Option<PathBuf> {
+        let id = self.assets_index_id(manager)?;
+        match AssetIndex::from_json_file(assets_dir.join(format!("indexes/{}.json", id)).as_path()) {
+            Result::Ok(index) => index.legacy_dir(&id, assets_dir, game_dir),
+            // without a downloaded index only the well known legacy ids can be recognized
+            Result::Err(_) => match id.as_str() {
+                "pre-1.6" => Some(game_dir.join("resources")),
+                "legacy" => Some(assets_dir.join("virtual/legacy")),
+                _ => None
+            }
+        }
+    }
+
+    pub fn materialize_legacy_assets(&self,
+                                     manager: &VersionManager,
+                                     assets_dir: &Path,
+                                     game_dir: &Path) -> Result<Option<PathBuf>, Error> {
+        let id = match self.assets_index_id(manager) {
+            Some(id) => id,
+            None => return Result::Ok(None)
+        };
+        let index = AssetIndex::from_json_file(assets_dir.join(format!("indexes/{}.json", id)).as_path())?;
+        let target_dir = match index.legacy_dir(&id, assets_dir, game_dir) {
+            Some(target_dir) => target_dir,
+            None => return Result::Ok(None)
+        };
+        for (name, object) in index.objects.iter() {
+            if !Path::new(name).components().all(|c| matches!(c, Component::Normal(_))) {
+                return Result::Err(Error::UnrecognizedPathString(OsString::from(name)));
+            }
+            let relative_path = object.relative_path().ok_or_else(|| Error::UnrecognizedHash(object.hash.clone()))?;
+            let target = target_dir.join(name);
+            if fs::metadata(target.as_path()).map(|m| m.len() as i64 == object.size).unwrap_or(false) { continue; }
+            if let Some(parent) = target.parent() { fs::create_dir_all(parent)? }
+            fs::copy(assets_dir.join("objects").join(&relative_path), target)?;
+        }
+        Result::Ok(Some(target_dir))
+    }
+
+    pub fn java_version(&self, manager: &VersionManager) -> Option<u32> {
+        self.java_version.as_ref().map(|v| v.major_version).or_else(|| {
+            if let Some(ref inherits_from) = self.inherits_from {
+                manager.version_of(inherits_from).ok().and_then(|v| v.java_version(manager))
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn main_class(&self, manager: &VersionManager) -> Option<String> {
+        self.main_class.clone().or_else(|| {
+            if let Some(ref inherits_from) = self.inherits_from {
+                manager.version_of(inherits_from).ok().and_then(|v| v.main_class(manager))
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn primary_jar_download(&self, manager: &VersionManager) -> Option<DownloadInfo> {
+        self.downloads.get("client").cloned().or_else(|| {
+            if let Some(ref inherits_from) = self.inherits_from {
+                manager.version_of(inherits_from).ok().and_then(|v| v.primary_jar_download(manager))
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn server_jar_download(&self, manager: &VersionManager) -> Option<DownloadInfo> {
+        self.downloads.get("server").cloned().or_else(|| {
+            if let Some(ref inherits_from) = self.inherits_from {
+                manager.version_of(inherits_from).ok().and_then(|v| v.server_jar_download(manager))
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn libraries(&self, manager: &VersionManager) -> Result<Vec<Library>, Error> {
+        if let Some(ref inherits_from) = self.inherits_from {
+            let mut result = manager.version_of(inherits_from)?.libraries(manager)?;
+            result.extend(self.libraries.clone());
+            Result::Ok(result)
+        } else {
+            Result::Ok(self.libraries.clone())
+        }
+    }
+
+    pub fn flatten(&self, manager: &VersionManager) -> Result<MinecraftVersion, Error> {
+        let mut result = self.clone();
+        result.version_jar = self.version_jar.clone().or_else(|| Some(self.id.clone()));
+        if let Some(ref inherits_from) = self.inherits_from {
+            let parent = manager.version_of(inherits_from)?.flatten(manager)?;
+            result.inherits_from = None;
+            result.version_jar = self.version_jar.clone().or(parent.version_jar);
+            result.main_class = self.main_class.clone().or(parent.main_class);
+            result.asset_index = self.asset_index(manager);
+            result.assets_id = result.asset_index.as_ref().map(|i| i.id().to_owned()).or_else(|| self.assets_id.clone());
+            result.libraries = parent.libraries;
+            result.libraries.extend(self.libraries.iter().cloned());
+            result.downloads = parent.downloads;
+            result.downloads.extend(self.downloads.iter().map(|(k, v)| (k.clone(), v.clone())));
+            result.arguments = match (parent.arguments, self.arguments.clone()) {
+                (Some(mut arguments), Some(child)) => {
+                    arguments.game.extend(child.game);
+                    arguments.jvm.extend(child.jvm);
+                    Some(arguments)
+                }
+                (arguments, child) => child.or(arguments)
+            };
+            // a modern argument template anywhere in the chain takes priority over inherited legacy arguments
+            let inherited_legacy = if result.arguments.is_none() { parent.minecraft_arguments } else { None };
+            result.minecraft_arguments = self.minecraft_arguments.clone().or(inherited_legacy);
+            result.java_version = self.java_version.clone().or(parent.java_version);
+            result.client_version = self.client_version.clone().or(parent.client_version);
+            result.compliance_level = self.compliance_level.or(parent.compliance_level);
+            result.minimum_launcher_version = self.minimum_launcher_version.or(parent.minimum_launcher_version);
+            result.extra = parent.extra;
+            result.extra.extend(self.extra.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+        Result::Ok(result)
+    }
+
+    pub fn libraries_for_current_platform(&self, manager: &VersionManager) -> Result<Vec<Library>, Error> {
+        let mut libs = self.libraries(manager)?;
+        libs.retain(|lib| lib.is_allowed_on(OS_ARCH, OS_PLATFORM));
+        Result::Ok(libs)
+    }
+
+    pub fn version_jar_path(&self, manager: &VersionManager) -> Result<PathBuf, Error> {
+        match self.version_jar {
+            Some(ref jar) => manager.get_primary_jar_path(jar),
+            None => if let Some(ref inherits_from) = self.inherits_from {
+                manager.version_of(inherits_from)?.version_jar_path(manager)
+            } else {
+                manager.get_primary_jar_path(&self.id)
+            }
+        }
+    }
+
+    fn modern_arguments<F>(&self, manager: &VersionManager, select: F) -> Result<Option<Vec<Argument>>, Error>
+        where F: Fn(&Arguments) -> &Vec<Argument> + Copy {
+        let mut result = match self.inherits_from {
+            Some(ref inherits_from) => manager.version_of(inherits_from)?.modern_arguments(manager, select)?,
+            None => None
+        };
+        if let Some(ref arguments) = self.arguments {
+            result.get_or_insert_with(Vec::new).extend(select(arguments).iter().cloned());
+        }
+        Result::Ok(result)
+    }
+
+    // the feature names referenced by argument rules, including the ones inherited from a parent version
+    pub fn required_features(&self, manager: &VersionManager) -> Result<HashSet<String>, Error> {
+        let mut result = HashSet::new();
+        for arguments in &[self.modern_arguments(manager, |a| &a.game)?, self.modern_arguments(manager, |a| &a.jvm)?] {
+            for argument in arguments.iter().flat_map(|a| a.iter()) {
+                if let Argument::Conditional { ref rules, .. } = *argument {
+                    result.extend(rules.iter().flat_map(|rule| rule.features.keys().cloned()));
+                }
+            }
+        }
+        Result::Ok(result)
+    }
+
+    pub fn collect_game_arguments(&self,
+                                  manager: &VersionManager,
+                                  parameters: &mut Vec<launcher::GameOption>,
+                                  features: &FeatureSet,
+                                  s: &parsing::ParameterStrategy) -> Result<(), Error> {
+        let mut option_name = None;
+        match self.minecraft_arguments {
+            Some(ref args) => {
+                for arg in parsing::parse_checked(args, s)? {
+                    // an empty value still belongs to its option, only a dangling one is dropped
+                    if arg.is_empty() && option_name.is_none() { continue; }
+                    match option_name {
+                        None => if arg.starts_with("-") {
+                            option_name = Some(arg);
+                        } else {
+                            (*parameters).push(launcher::GameOption::new_single(arg));
+                        }
+                        Some(name) => if arg.starts_with("-") {
+                            (*parameters).push(launcher::GameOption::new_single(name));
+                            option_name = Some(arg);
+                        } else {
+                            (*parameters).push(launcher::GameOption::new_pair(name, arg));
+                            option_name = None;
+                        }
+                    }
+                }
+                if let Some(name) = option_name {
+                    (*parameters).push(launcher::GameOption::new_single(name));
+                }
+                if features.get("has_custom_resolution") {
+                    parameters.push(launcher::GameOption::new_pair("--width".to_owned(), self.parse_token("${resolution_width}", s)));
+                    parameters.push(launcher::GameOption::new_pair("--height".to_owned(), self.parse_token("${resolution_height}", s)));
+                }
+            }
+            None => if let Some(arguments) = self.modern_arguments(manager, |a| &a.game)? {
+                for arg in Argument::resolve_all(&arguments, features, s) {
+                    match option_name {
+                        None => if arg.starts_with("-") {
+                            option_name = Some(arg);
+                        } else {
+                            parameters.push(launcher::GameOption::new_single(arg));
+                        }
+                        Some(name) => if arg.starts_with("-") {
+                            parameters.push(launcher::GameOption::new_single(name));
+                            option_name = Some(arg);
+                        } else {
+                            parameters.push(launcher::GameOption::new_pair(name, arg));
+                            option_name = None;
+                        }
+                    }
+                }
+                if let Some(name) = option_name {
+                    parameters.push(launcher::GameOption::new_single(name));
+                }
+            } else if let Some(ref inherits_from) = self.inherits_from {
+                let version = manager.version_of(inherits_from)?;
+                return version.collect_game_arguments(manager, parameters, features, s);
+            }
+        }
+        Result::Ok(())
+    }
+
+    pub fn collect_jvm_arguments(&self,
+                                 manager: &VersionManager,
+                                 parameters: &mut Vec<launcher::JvmOption>,
+                                 features: &FeatureSet,
+                                 system_properties: &HashMap<String, String>,
+                                 s: &parsing::ParameterStrategy) -> Result<(), Error> {
+        let mut properties: Vec<String> = system_properties.iter().map(|(k, v)| format!("-D{}={}", k, v)).collect();
+        properties.sort();
+        if let Some(arguments) = self.modern_arguments(manager, |a| &a.jvm)? {
+            let mut resolved = Argument::resolve_all(&arguments, features, s);
+            let classpath_index = resolved.iter().position(|a| a == "-cp" || a == "-classpath").unwrap_or(resolved.len());
+            resolved.splice(classpath_index..classpath_index, properties);
+            parameters.extend(resolved.into_iter().map(launcher::JvmOption::new));
+            return Result::Ok(());
+        }
+        if OS_PLATFORM == "windows" { parameters.push(launcher::JvmOption::new("-XX:HeapDumpPath=MojangTricksIntelDriversForPerformance_javaw.exe_minecraft.exe.heapdump".to_owned())); }
+        parameters.push(launcher::JvmOption::new(self.parse_token("-Djava.library.path=${natives_directory}", s)));
+        parameters.push(launcher::JvmOption::new(self.parse_token("-Dminecraft.launcher.brand=${launcher_name}", s)));
+        parameters.push(launcher::JvmOption::new(self.parse_token("-Dminecraft.launcher.version=${launcher_version}", s)));
+        parameters.push(launcher::JvmOption::new(self.parse_token("-Dminecraft.client.jar=${primary_jar}", s)));
+        parameters.extend(properties.into_iter().map(launcher::JvmOption::new));
+        parameters.push(launcher::JvmOption::new("-cp".to_owned()));
+        parameters.push(launcher::JvmOption::new(self.parse_token("${classpath}", s)));
+        Result::Ok(())
+    }
+
+    pub fn classpath(&self,
+                     library_path: &Path,
+                     manager: &VersionManager) -> Result<String, Error> {
+        self.classpath_with_separator(library_path, CLASSPATH_SEPARATOR, manager)
+    }
+
+    pub fn classpath_with_separator(&self,
+                                    library_path: &Path,
+                                    classpath_separator: &str,
+                                    manager: &VersionManager) -> Result<String, Error> {
+        join_classpath(&self.classpath_entries(library_path, manager)?, classpath_separator)
+    }
+
+    pub fn classpath_entries(&self,
+                             library_path: &Path,
+                             manager: &VersionManager) -> Result<Vec<PathBuf>, Error> {
+        let libs = self.libraries(manager)?;
+        let mut result = Vec::new();
+        for lib in libs.iter() {
+            if !lib.is_native() {
+                if let Some(path_buf) = lib.classpath_default(library_path) {
+                    result.push(fs::canonicalize(path_buf.as_path())?);
+                }
+            }
+        }
+        result.push(self.version_jar_path(manager)?);
+        Result::Ok(result)
+    }
+
+    pub fn download_targets(&self,
+                            manager: &VersionManager,
+                            library_path: &Path) -> Result<Vec<(PathBuf, DownloadInfo)>, Error> {
+        let mut result = Vec::new();
+        for lib in self.libraries(manager)? {
+            if let (Some(path_buf), Some(info)) = (lib.classpath_default(library_path), lib.download_info_default()) {
+                result.push((path_buf, info.clone()));
+            }
+        }
+        if let Some(info) = self.primary_jar_download(manager) {
+            result.push((self.version_jar_path(manager)?, info));
+        }
+        Result::Ok(result)
+    }
+
+    // returns the known size in bytes and the number of files whose size is unknown
+    pub fn estimated_download_size(&self,
+                                   manager: &VersionManager,
+                                   library_path: Option<&Path>) -> Result<(i64, usize), Error> {
+        let (mut size, mut unknown) = (0i64, 0usize);
+        for (path_buf, info) in self.download_targets(manager, library_path.unwrap_or(Path::new("")))? {
+            if library_path.is_some() && path_buf.is_file() { continue; }
+            match info.known_size() {
+                Some(file_size) => size += i64::from(file_size),
+                None => unknown += 1
+            }
+        }
+        if let Some(asset_index) = self.asset_index(manager) {
+            match (asset_index.size(), asset_index.total_size()) {
+                (Some(index_size), Some(total_size)) => size += index_size + total_size,
+                _ => unknown += 1
+            }
+        }
+        Result::Ok((size, unknown))
+    }
+
+    pub fn verify_libraries(&self,
+                            manager: &VersionManager,
+                            library_path: &Path) -> Result<Vec<(Library, VerifyStatus)>, Error> {
+        let mut result = Vec::new();
+        for lib in self.libraries(manager)? {
+            match lib.verify(library_path)? {
+                VerifyStatus::Ok => (),
+                status => result.push((lib, status))
+            }
+        }
+        Result::Ok(result)
+    }
+
+    pub fn to_native_collection(&self,
+                                manager: &VersionManager,
+                                library_path: &Path) -> Result<NativeCollection, Error> {
+        self.to_native_collection_with_excludes(manager, library_path, &[])
+    }
+
+    pub fn to_native_collection_with_excludes(&self,
+                                              manager: &VersionManager,
+                                              library_path: &Path,
+                                              extra_excludes: &[String]) -> Result<NativeCollection, Error> {
+        let extra_extract_ignored = extra_excludes.to_vec();
+        let mut collection = NativeCollection { libraries: Vec::new(), extra_extract_ignored };
+        for lib in self.natives_libraries(manager)?.iter() {
+            if let Some(path_buf) = lib.classpath_default(library_path) {
+                collection.libraries.push((path_buf, lib.extract_ignored.clone(), lib.has_natives_classifier()))
+            }
+        }
+        Result::Ok(collection)
+    }
+
+    pub fn natives_libraries(&self, manager: &VersionManager) -> Result<Vec<Library>, Error> {
+        let libraries = self.libraries(manager)?;
+        let natives = libraries.into_iter().filter(|lib| lib.is_native() && lib.download_info_default().is_some()).collect();
+        Result::Ok(Library::select_natives(natives, OS_PLATFORM, OS_ARCH_NAME))
+    }
+
+    fn parse_token(&self, token: &str, s: &parsing::ParameterStrategy) -> String {
+        match parsing::parse(token, s).next() {
+            Some(parsed_token) => parsed_token,
+            None => token.to_owned()
+        }
+    }
+}
+
+impl Argument {
+    fn resolve_all(arguments: &[Argument], features: &FeatureSet, s: &parsing::ParameterStrategy) -> Vec<String> {
+        let mut result = Vec::new();
+        for argument in arguments.iter() {
+            match *argument {
+                Argument::Plain(ref value) => result.push(parsing::substitute(value, s)),
+                Argument::Conditional { ref rules, ref value } => if Rule::is_allowed(rules, features) {
+                    match *value {
+                        ArgumentValue::Single(ref value) => result.push(parsing::substitute(value, s)),
+                        ArgumentValue::Multiple(ref values) => {
+                            result.extend(values.iter().map(|value| parsing::substitute(value, s)))
+                        }
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+impl Rule {
+    pub fn is_allowed(rules: &[Rule], features: &FeatureSet) -> bool {
+        let mut allowed = rules.is_empty();
+        for rule in rules.iter() {
+            if rule.matches(features) {
+                allowed = rule.action == "allow";
+            }
+        }
+        allowed
+    }
+
+    fn matches(&self, features: &FeatureSet) -> bool {
+        if let Some(ref os) = self.os {
+            if !os.matches() { return false; }
+        }
+        self.features.iter().all(|(name, &expected)| features.get(name) == expected)
+    }
+}
+
+impl OsRule {
+    fn matches(&self) -> bool {
+        let name_matches = match self.name {
+            Some(ref name) => name == OS_PLATFORM || (name == "osx" && OS_PLATFORM == "macos"),
+            None => true
+        };
+        let arch_matches = match self.arch {
+            Some(ref arch) if arch == "x86" => OS_ARCH == "32",
+            Some(ref arch) => arch == env::consts::ARCH,
+            None => true
+        };
+        name_matches && arch_matches // os version patterns are not checked
+    }
+}
+
+impl FeatureSet {
+    pub fn new() -> FeatureSet {
+        FeatureSet(HashMap::new())
+    }
+
+    pub fn with(mut self, name: &str, value: bool) -> FeatureSet {
+        self.0.insert(name.to_owned(), value);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> bool {
+        self.0.get(name).cloned().unwrap_or(false)
+    }
+}
+
+impl AssetIndex {
+    pub fn from_json_file(path: &Path) -> Result<AssetIndex, Error> {
+        if !path.is_file() { return Result::Err(Error::FileUnavailableError(Box::from(path))); }
+        Result::Ok(serde_json::from_reader(fs::File::open(path)?)?)
+    }
+
+    pub fn objects(&self) -> &HashMap<String, AssetObject> {
+        &self.objects
+    }
+
+    pub fn is_virtual(&self) -> bool {
+        self.is_virtual
+    }
+
+    pub fn map_to_resources(&self) -> bool {
+        self.map_to_resources
+    }
+
+    pub fn legacy_dir(&self, id: &str, assets_dir: &Path, game_dir: &Path) -> Option<PathBuf> {
+        if self.map_to_resources {
+            Some(game_dir.join("resources"))
+        } else if self.is_virtual {
+            Some(assets_dir.join("virtual").join(id))
+        } else {
+            None
+        }
+    }
+}
+
+impl AssetObject {
+    pub fn hash(&self) -> &str {
+        &self.hash
+    }
+
+    pub fn size(&self) -> i64 {
+        self.size
+    }
+
+    pub fn relative_path(&self) -> Option<String> {
+        if self.hash.len() != 40 || !self.hash.chars().all(|c| c.is_ascii_hexdigit()) { return None; }
+        Some(format!("{}/{}", &self.hash[..2], self.hash))
+    }
+}
+
+impl AssetDownloadInfo {
+    pub fn new(id: String) -> AssetDownloadInfo {
+        AssetDownloadInfo {
+            size: None,
+            url: None,
+            sha1: None,
+            asset_index_id: id,
+            total_size: None,
+            size_and_hash_known: false,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.asset_index_id
+    }
+
+    pub fn size(&self) -> Option<i64> {
+        self.size.map(i64::from)
+    }
+
+    pub fn sha1(&self) -> Option<&str> {
+        self.sha1.as_deref()
+    }
+
+    pub fn total_size(&self) -> Option<i64> {
+        self.total_size
+    }
+}
+
+impl From<AssetDownloadInfo> for DownloadInfo {
+    fn from(info: AssetDownloadInfo) -> Self {
+        let id = info.asset_index_id;
+        match (info.size, info.url, info.sha1) {
+            (Some(size), Some(url), Some(sha1)) => DownloadInfo::PreHashed { size, url, checksum: Checksum::sha1(sha1) },
+            (_, Some(url), _) => DownloadInfo::Raw { url },
+            _ => DownloadInfo::Raw {
+                url: mirrors::MirrorConfig::official().legacy_assets_url(&format!("indexes/{}.json", id)),
+            }
+        }
+    }
+}
+
+impl VersionManifest {
+    pub fn latest_id(&self, version_type: VersionType) -> Option<&str> {
+        match version_type {
+            VersionType::Release => Some(&self.latest.release),
+            VersionType::Snapshot => Some(&self.latest.snapshot),
+            _ => None
+        }
+    }
+
+    pub fn latest(&self, version_type: VersionType) -> Option<&VersionManifestEntry> {
+        self.latest_id(version_type).and_then(|id| self.get(id))
+    }
+
+    pub fn get(&self, id: &str) -> Option<&VersionManifestEntry> {
+        self.versions.iter().find(|v| v.id == id)
+    }
+
+    pub fn versions(&self) -> &[VersionManifestEntry] {
+        &self.versions
+    }
+
+    pub fn filter_by_type(&self, version_type: VersionType) -> Vec<&VersionManifestEntry> {
+        self.versions.iter().filter(|v| v.version_type == version_type).collect()
+    }
+}
+
+impl VersionManifestEntry {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn version_type(&self) -> VersionType {
+        self.version_type
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
     }
 
     pub fn publish_time(&self) -> &str {
@@ -217,194 +1432,112 @@ impl MinecraftVersion {
         &self.release_time
     }
 
-    pub fn asset_index(&self, manager: &VersionManager) -> Option<AssetDownloadInfo> {
-        self.asset_index.clone().or_else(|| self.assets_id.clone().map(AssetDownloadInfo::new)).or_else(|| {
-            if let Some(ref inherits_from) = self.inherits_from {
-                manager.version_of(&inherits_from).ok().and_then(|v| v.asset_index(manager))
-            } else {
-                None
-            }
-        })
+    pub fn sha1(&self) -> Option<&str> {
+        self.sha1.as_deref()
     }
 
-    pub fn main_class(&self, manager: &VersionManager) -> Option<String> {
-        self.main_class.clone().or_else(|| {
-            if let Some(ref inherits_from) = self.inherits_from {
-                manager.version_of(&inherits_from).ok().and_then(|v| v.main_class(manager))
-            } else {
-                None
-            }
-        })
+    pub fn compliance_level(&self) -> Option<u32> {
+        self.compliance_level
     }
+}
 
-    pub fn libraries(&self, manager: &VersionManager) -> Result<Vec<Library>, Error> {
-        if let Some(ref inherits_from) = self.inherits_from {
-            let mut result = manager.version_of(&inherits_from)?.libraries(manager)?;
-            result.extend(self.libraries.clone().into_iter());
-            Result::Ok(result)
-        } else {
-            Result::Ok(self.libraries.clone())
+impl DownloadInfo {
+    pub fn size(&self) -> Option<i64> {
+        match *self {
+            DownloadInfo::PreHashed { size, .. } => Some(i64::from(size)),
+            _ => None
         }
     }
 
-    pub fn version_jar_path(&self, manager: &VersionManager) -> Result<PathBuf, Error> {
-        match self.version_jar {
-            Some(ref jar) => {
-                let version_path = manager.get_version_path();
-                Result::Ok(version_path.join(format!("{0}/{0}.jar", jar)))
-            },
-            None => if let Some(ref inherits_from) = self.inherits_from {
-                manager.version_of(&inherits_from)?.version_jar_path(manager)
-            } else {
-                let version_path = manager.get_version_path();
-                Result::Ok(version_path.join(format!("{0}/{0}.jar", self.id)))
-            }
+    // none when the metadata gives no usable size, so a planner has to issue a HEAD request for it
+    pub fn known_size(&self) -> Option<i32> {
+        match *self {
+            DownloadInfo::PreHashed { size, .. } if size > 0 => Some(size),
+            _ => None
         }
     }
 
-    pub fn collect_game_arguments(&self,
-                                  manager: &VersionManager,
-                                  parameters: &mut Vec<launcher::GameOption>,
-                                  s: &parsing::ParameterStrategy) -> Result<(), Error> {
-        let mut option_name = None;
-        match self.minecraft_arguments {
-            Some(ref args) => {
-                for arg in parsing::parse(&args, s) {
-                    if arg.is_empty() { return Result::Ok(()); }
-                    match option_name {
-                        None => if arg.starts_with("-") {
-                            option_name = Some(arg);
-                        } else {
-                            (*parameters).push(launcher::GameOption::new_single(arg));
-                        }
-                        Some(name) => if arg.starts_with("-") {
-                            (*parameters).push(launcher::GameOption::new_single(name));
-                            option_name = Some(arg);
-                        } else {
-                            (*parameters).push(launcher::GameOption::new_pair(name, arg));
-                            option_name = None;
-                        }
-                    }
-                }
-                if let Some(name) = option_name {
-                    (*parameters).push(launcher::GameOption::new_single(name));
-                }
-                parameters.push(launcher::GameOption::new_pair("--width".to_owned(), self.parse_token("${resolution_width}", s)));
-                parameters.push(launcher::GameOption::new_pair("--height".to_owned(), self.parse_token("${resolution_height}", s)));
-            }
-            None => if let Some(ref inherits_from) = self.inherits_from {
-                let version = manager.version_of(&inherits_from)?;
-                return version.collect_game_arguments(manager, parameters, s);
-            }
+    pub fn sha1(&self) -> Option<&str> {
+        match *self {
+            DownloadInfo::PreHashed { ref checksum, .. } => checksum.get(HashAlgo::Sha1),
+            _ => None
         }
-        Result::Ok(())
-    }
-
-    pub fn collect_jvm_arguments(&self,
-                                 _: &VersionManager,
-                                 parameters: &mut Vec<launcher::JvmOption>,
-                                 s: &parsing::ParameterStrategy) -> Result<(), Error> {
-        if OS_PLATFORM == "windows" { parameters.push(launcher::JvmOption::new("-XX:HeapDumpPath=MojangTricksIntelDriversForPerformance_javaw.exe_minecraft.exe.heapdump".to_owned())); }
-        parameters.push(launcher::JvmOption::new(self.parse_token("-Djava.library.path=${natives_directory}", s)));
-        parameters.push(launcher::JvmOption::new(self.parse_token("-Dminecraft.launcher.brand=${launcher_name}", s)));
-        parameters.push(launcher::JvmOption::new(self.parse_token("-Dminecraft.launcher.version=${launcher_version}", s)));
-        parameters.push(launcher::JvmOption::new(self.parse_token("-Dminecraft.client.jar=${primary_jar}", s)));
-        parameters.push(launcher::JvmOption::new("-cp".to_owned()));
-        parameters.push(launcher::JvmOption::new(self.parse_token("${classpath}", s)));
-        Result::Ok(())
-    }
-
-    pub fn classpath(&self,
-                     library_path: &Path,
-                     manager: &VersionManager) -> Result<String, Error> {
-        self.classpath_with_separator(library_path, CLASSPATH_SEPARATOR, manager)
     }
 
-    pub fn classpath_with_separator(&self,
-                                    library_path: &Path,
-                                    classpath_separator: &str,
-                                    manager: &VersionManager) -> Result<String, Error> {
-        let libs = self.libraries(manager)?;
-        let mut result = String::new();
-        for lib in libs.iter() {
-            if !lib.is_native() {
-                if let Some(path_buf) = lib.classpath_default(library_path) {
-                    let path = fs::canonicalize(path_buf.as_path())?.into_os_string();
-                    result.push_str(&path.into_string()?);
-                    result.push_str(classpath_separator);
-                }
-            }
+    pub fn checksum(&self) -> Option<&Checksum> {
+        match *self {
+            DownloadInfo::PreHashed { ref checksum, .. } => Some(checksum),
+            _ => None
         }
-        let primary_jar_path = self.version_jar_path(manager)?.into_os_string();
-        result.push_str(primary_jar_path.into_string()?.as_str());
-        Result::Ok(result)
     }
 
-    pub fn to_native_collection(&self,
-                                manager: &VersionManager,
-                                library_path: &Path) -> Result<NativeCollection, Error> {
-        let mut collection = NativeCollection { libraries: Vec::new() };
-        for lib in self.libraries(manager)?.iter() {
-            if lib.is_native() {
-                if let Some(path_buf) = lib.classpath_default(library_path) {
-                    collection.libraries.push((path_buf, lib.extract_ignored.clone()))
-                }
-            }
+    pub fn url(&self) -> &str {
+        match *self {
+            DownloadInfo::PreHashed { ref url, .. } => url,
+            DownloadInfo::RawXzip { ref url } => url,
+            DownloadInfo::Raw { ref url } => url,
         }
-        Result::Ok(collection)
     }
 
-    fn parse_token(&self, token: &str, s: &parsing::ParameterStrategy) -> String {
-        match parsing::parse(token, s).next() {
-            Some(parsed_token) => parsed_token,
-            None => token.to_owned()
+    pub fn with_mirror(&self, mirror: &mirrors::MirrorConfig) -> DownloadInfo {
+        match *self {
+            DownloadInfo::PreHashed { size, ref url, ref checksum } => {
+                DownloadInfo::PreHashed { size, url: mirror.rewrite(url), checksum: checksum.clone() }
+            }
+            DownloadInfo::RawXzip { ref url } => DownloadInfo::RawXzip { url: mirror.rewrite(url) },
+            DownloadInfo::Raw { ref url } => DownloadInfo::Raw { url: mirror.rewrite(url) },
         }
     }
 }
 
-impl AssetDownloadInfo {
-    pub fn new(id: String) -> AssetDownloadInfo {
-        AssetDownloadInfo {
-            size: None,
-            url: None,
-            sha1: None,
-            asset_index_id: id,
-            total_size: None,
-            size_and_hash_known: false,
+impl RuleDecision {
+    pub fn is_allowed(&self) -> bool {
+        match *self {
+            RuleDecision::Allowed(_) => true,
+            RuleDecision::Denied(_) => false,
         }
     }
-
-    pub fn id(&self) -> &str {
-        &self.asset_index_id
-    }
 }
 
-impl From<AssetDownloadInfo> for DownloadInfo {
-    fn from(info: AssetDownloadInfo) -> Self {
-        let id = info.asset_index_id;
-        match (info.size, info.url, info.sha1, info.size_and_hash_known) {
-            (Some(size), Some(url), Some(sha1), true) => DownloadInfo::PreHashed { size, url, sha1 },
-            (_, Some(url), _, _) => DownloadInfo::Raw { url },
-            _ => DownloadInfo::Raw {
-                url: format!("https://s3.amazonaws.com/Minecraft.Download/indexes/{}.json", id),
-            }
+impl fmt::Display for RuleDecision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RuleDecision::Allowed(Some(ref rule)) => write!(f, "allowed by rule {}", rule),
+            RuleDecision::Allowed(None) => write!(f, "allowed (no rules)"),
+            RuleDecision::Denied(Some(ref rule)) => write!(f, "disallowed by rule {}", rule),
+            RuleDecision::Denied(None) => write!(f, "disallowed"),
         }
     }
 }
 
 impl DownloadStrategy {
-    fn get<'a>(&'a self, arg: &str) -> Option<(&'a str, &'a DownloadInfo)> {
-        let mut allowed = self.rules.is_empty();
-        for &(ref action, ref os) in &self.rules {
+    pub fn explain(&self, arch: &str, platform: &str) -> RuleDecision {
+        let mut decision = if self.rules.is_empty() { RuleDecision::Allowed(None) } else { RuleDecision::Denied(None) };
+        for (action, os, os_arch) in &self.rules {
+            let os_matches = os.is_empty() || normalize_platform(os) == normalize_platform(platform);
+            let arch_matches = os_arch.is_empty() || os_arch == arch || (os_arch == "x86" && arch == "32");
+            let mut rule = action.clone();
+            if !os.is_empty() { rule.push_str(&format!(" os.name={}", os)) }
+            if !os_arch.is_empty() { rule.push_str(&format!(" os.arch={}", os_arch)) }
             match action.as_str() {
-                "allow" => allowed = os.is_empty() || os == OS_PLATFORM,
-                "disallow" => allowed = !os.is_empty() && os != OS_PLATFORM,
+                "allow" if os_matches && arch_matches => decision = RuleDecision::Allowed(Some(rule)),
+                "allow" => decision = RuleDecision::Denied(Some(rule)),
+                "disallow" if os_matches && arch_matches => decision = RuleDecision::Denied(Some(rule)),
+                "disallow" => decision = RuleDecision::Allowed(Some(rule)),
                 _ => () // just ignore it
             }
         }
-        if allowed {
+        decision
+    }
+
+    fn is_allowed_on(&self, arch: &str, platform: &str) -> bool {
+        self.explain(arch, platform).is_allowed()
+    }
+
+    fn get<'a>(&'a self, arg: &str) -> Option<(&'a str, &'a DownloadInfo)> {
+        if self.is_allowed_on(OS_ARCH, OS_PLATFORM) {
             match self.with_classifier.get(arg) {
-                Some(&(ref classifier, ref info)) => Some((&classifier, &info)),
+                Some((classifier, info)) => Some((classifier, info)),
                 None => self.default.as_ref().map(|v| ("", v))
             }
         } else {
@@ -414,10 +1547,22 @@ impl DownloadStrategy {
 }
 
 impl Library {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn relative_path(&self) -> Option<PathBuf> {
+        Library::get_url_suffix(&self.name, "", false).map(PathBuf::from)
+    }
+
     pub fn is_native(&self) -> bool {
         self.is_native
     }
 
+    pub fn extract_excludes(&self) -> &[String] {
+        self.extract_ignored.as_slice()
+    }
+
     pub fn download_info_default(&self) -> Option<&DownloadInfo> {
         self.download_info_of(OS_ARCH, OS_PLATFORM)
     }
@@ -429,6 +1574,18 @@ impl Library {
         }
     }
 
+    pub fn download_info_with_mirror(&self, mirror: &mirrors::MirrorConfig) -> Option<DownloadInfo> {
+        self.download_info_default().map(|info| info.with_mirror(mirror))
+    }
+
+    pub fn is_allowed_on(&self, arch: &str, platform: &str) -> bool {
+        self.downloads.is_allowed_on(arch, platform)
+    }
+
+    pub fn explain_rules(&self, arch: &str, platform: &str) -> RuleDecision {
+        self.downloads.explain(arch, platform)
+    }
+
     pub fn classpath_default(&self, path: &Path) -> Option<PathBuf> {
         self.classpath_of(path, OS_ARCH, OS_PLATFORM)
     }
@@ -447,15 +1604,69 @@ impl Library {
         }
     }
 
+    pub fn verify(&self, library_path: &Path) -> Result<VerifyStatus, Error> {
+        match (self.classpath_default(library_path), self.download_info_default()) {
+            (Some(path_buf), Some(info)) => verify_file(path_buf.as_path(), info.checksum()),
+            _ => Result::Ok(VerifyStatus::Ok) // not used on this platform
+        }
+    }
+
     fn get_as_result<E: de::Error>(v: &Value, expected: &str) -> Result<String, E> {
         v.as_str().map(String::from).ok_or_else(|| {
             de::Error::invalid_type(de::Unexpected::UnitVariant, &expected)
         })
     }
 
+    pub fn has_natives_classifier(&self) -> bool {
+        Library::natives_classifier(&self.name).is_some()
+    }
+
+    fn natives_classifier(name: &str) -> Option<&str> {
+        name.splitn(4, ':').nth(3).filter(|classifier| classifier.starts_with("natives-"))
+    }
+
+    // 2 for natives-<os>-<arch>, 1 for the bare natives-<os>, None for other platforms
+    fn natives_classifier_rank(classifier: &str, platform: &str, arch: &str) -> Option<u8> {
+        let rest = classifier.trim_start_matches("natives-");
+        if rest == platform {
+            Some(1)
+        } else if rest.starts_with(platform) && rest[platform.len()..] == format!("-{}", arch) {
+            Some(2)
+        } else {
+            None
+        }
+    }
+
+    fn select_natives(libraries: Vec<Library>, platform: &str, arch: &str) -> Vec<Library> {
+        // the same artifact may be listed once per arch, only the closest match is kept
+        let mut best: HashMap<String, u8> = HashMap::new();
+        for lib in libraries.iter() {
+            if let Some(classifier) = Library::natives_classifier(&lib.name) {
+                if let Some(rank) = Library::natives_classifier_rank(classifier, platform, arch) {
+                    let artifact = lib.name[..lib.name.len() - classifier.len()].to_owned();
+                    let entry = best.entry(artifact).or_insert(rank);
+                    if *entry < rank {
+                        *entry = rank;
+                    }
+                }
+            }
+        }
+        libraries.into_iter().filter(|lib| match Library::natives_classifier(&lib.name) {
+            Some(classifier) => {
+                let artifact = &lib.name[..lib.name.len() - classifier.len()];
+                match Library::natives_classifier_rank(classifier, platform, arch) {
+                    Some(rank) => best.get(artifact) == Some(&rank),
+                    None => false
+                }
+            }
+            None => true
+        }).collect()
+    }
+
     fn get_url_suffix(name: &str, classifier: &str, is_xz: bool) -> Option<String> {
-        let parts: Vec<_> = name.splitn(3, ':').collect();
-        if parts.len() != 3 { None } else {
+        let parts: Vec<_> = name.splitn(4, ':').collect();
+        if parts.len() < 3 { None } else {
+            let classifier = if classifier.is_empty() { parts.get(3).cloned().unwrap_or("") } else { classifier };
             let suffix = if is_xz { "jar.pack.xz" } else { "jar" };
             let dir = format!("{}/{}/{}", parts[0].replace(".", "/"), parts[1], parts[2]);
             if classifier.is_empty() {
@@ -473,12 +1684,14 @@ impl Library {
         let mut downloads: Value = Value::Null;
         let mut name = String::new();
         let mut extract_ignored = Vec::new();
+        let mut raw = serde_json::Map::new();
         let mut library_downloads = DownloadStrategy {
             with_classifier: HashMap::new(),
             rules: Vec::new(),
             default: None,
         };
         while let Some((key, value)) = map.next_entry::<String, Value>()? {
+            raw.insert(key.clone(), value.clone());
             match key.as_str() {
                 "name" => name = Library::get_as_result(&value, "library name")?,
                 "url" => url_prefix = Library::get_as_result(&value, "library url prefix")?,
@@ -502,20 +1715,21 @@ impl Library {
                         if let Some(map) = v.as_object() {
                             if let Some(value) = map.get("action") {
                                 let action = Library::get_as_result(value, "rule action")?;
-                                if let Some(os) = map.get("os").and_then(|v| {
-                                    v.as_object().and_then(|v| v.get("name"))
-                                }).map(|v| Library::get_as_result(v, "rule os")) {
-                                    library_downloads.rules.push((action, os?));
-                                } else {
-                                    library_downloads.rules.push((action, String::new()));
-                                }
+                                let os = map.get("os").and_then(|v| v.as_object());
+                                let os_name = match os.and_then(|v| v.get("name")) {
+                                    Some(v) => Library::get_as_result(v, "rule os")?,
+                                    None => String::new()
+                                };
+                                let os_arch = match os.and_then(|v| v.get("arch")) {
+                                    Some(v) => Library::get_as_result(v, "rule arch")?,
+                                    None => String::new()
+                                };
+                                library_downloads.rules.push((action, os_name, os_arch));
                             }
                         }
                     }
                 }
-                "downloads" => if value.is_object() {
-                    downloads = value.clone();
-                }
+                "downloads" if value.is_object() => downloads = value.clone(),
                 _ => () // just ignore it
             }
         }
@@ -523,6 +1737,10 @@ impl Library {
             let err = de::Error::invalid_type(de::Unexpected::UnitVariant, &"library name");
             return Result::Err(err);
         }
+        let is_native = !natives.is_empty() || Library::natives_classifier(&name).is_some();
+        if is_native && natives.is_empty() && extract_ignored.is_empty() {
+            extract_ignored.push("META-INF/".to_owned());
+        }
         if url_prefix.is_empty() {
             if let Some(map) = downloads.as_object() {
                 if let Some(classifiers) = map.get("classifiers").and_then(|v| v.as_object()) {
@@ -550,9 +1768,10 @@ impl Library {
                 }
                 return Result::Ok(Library {
                     name,
-                    is_native: !natives.is_empty(),
+                    is_native,
                     downloads: Rc::new(library_downloads),
                     extract_ignored: Rc::new(extract_ignored),
+                    raw: Rc::new(Value::Object(raw)),
                 });
             }
             url_prefix.push_str("https://libraries.minecraft.net/");
@@ -587,13 +1806,80 @@ impl Library {
         }
         Result::Ok(Library {
             name,
-            is_native: !natives.is_empty(),
+            is_native,
             downloads: Rc::new(library_downloads),
             extract_ignored: Rc::new(extract_ignored),
+            raw: Rc::new(Value::Object(raw)),
         })
     }
 }
 
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+fn normalize_platform(name: &str) -> &str {
+    if name == "osx" { "macos" } else { name }
+}
+
+fn check_version_id(id: &str) -> Result<(), Error> {
+    let mut components = Path::new(id).components();
+    let is_single_name = matches!((components.next(), components.next()), (Some(Component::Normal(_)), None));
+    if !is_single_name || id.contains(['/', '\\']) || id.contains("..") {
+        return Result::Err(Error::InvalidVersionId(id.to_owned()));
+    }
+    Result::Ok(())
+}
+
+pub fn join_classpath(entries: &[PathBuf], classpath_separator: &str) -> Result<String, Error> {
+    let mut result = String::new();
+    for (i, entry) in entries.iter().enumerate() {
+        if i > 0 { result.push_str(classpath_separator) }
+        result.push_str(&entry.clone().into_os_string().into_string()?);
+    }
+    Result::Ok(result)
+}
+
+// the metadata does not say whether a server jar is a bundler, so the downloaded jar has to be inspected
+pub fn is_bundled_server(jar_path: &Path) -> Result<bool, Error> {
+    let mut zip = ZipArchive::new(fs::File::open(jar_path)?)?;
+    let mut manifest = String::new();
+    match zip.by_name("META-INF/MANIFEST.MF") {
+        Result::Ok(mut file) => { io::Read::read_to_string(&mut file, &mut manifest)?; }
+        Result::Err(ZipError::FileNotFound) => return Result::Ok(false),
+        Result::Err(e) => return Result::Err(Error::from(e)),
+    }
+    Result::Ok(manifest.lines().any(|line| {
+        let mut parts = line.splitn(2, ':');
+        parts.next().map(str::trim) == Some("Main-Class") && parts.next().map(str::trim) == Some(BUNDLER_MAIN_CLASS)
+    }))
+}
+
+fn missing_asset_objects(index: &AssetIndex, assets_dir: &Path) -> Result<Vec<AssetObject>, Error> {
+    let mut result = Vec::new();
+    let mut seen = HashSet::new();
+    for object in index.objects.values() {
+        if !seen.insert(object.hash.as_str()) { continue; }
+        let relative_path = object.relative_path().ok_or_else(|| Error::UnrecognizedHash(object.hash.clone()))?;
+        let path_buf = assets_dir.join("objects").join(&relative_path);
+        // comparing sizes first avoids hashing files that are obviously truncated
+        let size_matches = fs::metadata(path_buf.as_path()).map(|m| m.len() as i64 == object.size).unwrap_or(false);
+        if size_matches && verify_file(path_buf.as_path(), Some(&Checksum::sha1(object.hash.clone())))? == VerifyStatus::Ok { continue; }
+        result.push(object.clone());
+    }
+    Result::Ok(result)
+}
+
+fn verify_file(path: &Path, checksum: Option<&Checksum>) -> Result<VerifyStatus, Error> {
+    if !path.is_file() { return Result::Ok(VerifyStatus::Missing); }
+    if let Some(expected) = checksum {
+        if !expected.matches(&checksum::hash_file(path, expected.algo())?) {
+            return Result::Ok(VerifyStatus::Mismatch);
+        }
+    }
+    Result::Ok(VerifyStatus::Ok)
+}
+
 impl<'de> Deserialize<'de> for Library {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
         struct LibraryVisitor;
@@ -613,3 +1899,577 @@ impl<'de> Deserialize<'de> for Library {
         deserializer.deserialize_map(LibraryVisitor)
     }
 }
+
+impl Serialize for Library {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.raw.serialize(serializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ops::Deref;
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static TEMP_DIR_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    // a fresh directory per test that is removed again even when the test fails
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let id = TEMP_DIR_COUNTER.fetch_add(1, Ordering::SeqCst);
+            let path_buf = env::temp_dir().join(format!("rmcll-{}-{}-{}", name, process::id(), id));
+            fs::create_dir_all(path_buf.as_path()).unwrap();
+            TempDir(path_buf)
+        }
+    }
+
+    impl Deref for TempDir {
+        type Target = PathBuf;
+
+        fn deref(&self) -> &PathBuf {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(self.0.as_path());
+        }
+    }
+
+    fn jvm_arguments(json: &str) -> Vec<String> {
+        let version: MinecraftVersion = serde_json::from_str(json).unwrap();
+        let manager = VersionManager::new(Path::new("versions/"));
+        let strategy = parsing::ParameterStrategy::map(|s| format!("<{}>", s));
+        let mut parameters = Vec::new();
+        let mut properties = HashMap::new();
+        properties.insert("user.home".to_owned(), "/sandbox".to_owned());
+        properties.insert("java.io.tmpdir".to_owned(), "/sandbox/tmp".to_owned());
+        version.collect_jvm_arguments(&manager, &mut parameters, &FeatureSet::new(), &properties, &strategy).unwrap();
+        parameters.iter().map(|option| option.to_string()).collect()
+    }
+
+    fn game_arguments(json: &str, features: &FeatureSet) -> Vec<String> {
+        let version: MinecraftVersion = serde_json::from_str(json).unwrap();
+        let manager = VersionManager::new(Path::new("versions/"));
+        let strategy = parsing::ParameterStrategy::map(|s| match s.as_str() {
+            "resolution_width" => "1280".to_owned(),
+            "resolution_height" => "720".to_owned(),
+            _ => format!("<{}>", s)
+        });
+        let mut parameters = Vec::new();
+        version.collect_game_arguments(&manager, &mut parameters, features, &strategy).unwrap();
+        parameters.iter().map(|option| option.to_string()).collect()
+    }
+
+    #[test]
+    fn inherited_fields() {
+        let root = TempDir::new("inherited-fields");
+        let manager = VersionManager::new(root.as_path());
+        let versions = [
+            ("1.12.2", r#"{
+                "id": "1.12.2", "type": "release", "time": "", "releaseTime": "",
+                "assets": "1.12", "mainClass": "net.minecraft.client.main.Main",
+                "assetIndex": { "id": "1.12", "url": "https://example.com/1.12.json", "sha1": "", "size": 0 }
+            }"#),
+            ("1.12.2-forge", r#"{
+                "id": "1.12.2-forge", "type": "release", "time": "", "releaseTime": "",
+                "inheritsFrom": "1.12.2", "assets": "1.12", "mainClass": "net.minecraft.launchwrapper.Launch"
+            }"#),
+            ("modpack", r#"{
+                "id": "modpack", "type": "release", "time": "", "releaseTime": "",
+                "inheritsFrom": "1.12.2-forge"
+            }"#),
+        ];
+        for &(id, json) in versions.iter() {
+            fs::create_dir_all(root.join(id)).unwrap();
+            fs::write(root.join(format!("{0}/{0}.json", id)), json).unwrap();
+        }
+        assert_eq!(manager.inherits_from("1.12.2-forge").unwrap(), Some("1.12.2".to_owned()));
+        assert_eq!(manager.inherits_from("1.12.2").unwrap(), None);
+        let version = manager.version_of("modpack").unwrap();
+        assert_eq!(version.inherits_from(), Some("1.12.2-forge"));
+        let asset_index = version.asset_index(&manager).unwrap();
+        assert_eq!(asset_index.id(), "1.12");
+        assert_eq!(version.assets_index_id(&manager), Some("1.12".to_owned()));
+        assert_eq!(asset_index.url, Some("https://example.com/1.12.json".to_owned()));
+        assert_eq!(version.main_class(&manager), Some("net.minecraft.launchwrapper.Launch".to_owned()));
+        assert_eq!(version.version_jar_path(&manager).unwrap(), manager.get_primary_jar_path("1.12.2").unwrap());
+        let flattened = version.flatten(&manager).unwrap();
+        assert_eq!(flattened.asset_index(&manager).unwrap().id(), "1.12");
+        assert_eq!(flattened.main_class(&manager), Some("net.minecraft.launchwrapper.Launch".to_owned()));
+        assert_eq!(flattened.version_jar_path(&manager).unwrap(), manager.get_primary_jar_path("1.12.2").unwrap());
+    }
+
+    #[test]
+    fn remove_version() {
+        let root = TempDir::new("remove-version");
+        let manager = VersionManager::new(root.join("versions").as_path());
+        fs::create_dir_all(root.join("versions/1.12.2")).unwrap();
+        fs::create_dir_all(root.join("outside")).unwrap();
+        fs::write(root.join("versions/1.12.2/1.12.2.json"), "{}").unwrap();
+        assert!(manager.remove_version("../outside").is_err());
+        assert!(manager.remove_version("..").is_err());
+        assert!(manager.version_of("/etc/passwd").is_err());
+        assert!(manager.version_of("..\\outside").is_err());
+        assert!(root.join("outside").is_dir());
+        manager.remove_version("1.12.2").unwrap();
+        assert!(!root.join("versions/1.12.2").exists());
+    }
+
+    #[test]
+    fn prune_natives() {
+        let root = TempDir::new("prune-natives");
+        let manager = VersionManager::new(root.as_path());
+        let current = manager.get_natives_path("1.12.2").unwrap();
+        fs::create_dir_all(current.as_path()).unwrap();
+        fs::create_dir_all(root.join("1.12.2/1.12.2-natives-stale-32")).unwrap();
+        fs::create_dir_all(root.join("1.7.10/1.7.10-natives-stale-64")).unwrap();
+        fs::write(root.join("1.12.2/1.12.2-natives-file"), "").unwrap();
+        assert_eq!(manager.prune_natives("1.12.2").unwrap(), vec![root.join("1.12.2/1.12.2-natives-stale-32")]);
+        assert_eq!(manager.prune_all_natives().unwrap(), vec![root.join("1.7.10/1.7.10-natives-stale-64")]);
+        assert!(current.is_dir());
+        assert!(root.join("1.12.2/1.12.2-natives-file").is_file());
+        assert!(!root.join("1.12.2/1.12.2-natives-stale-32.pruning").exists());
+    }
+
+    #[test]
+    fn version_source() {
+        let root = TempDir::new("version-source");
+        fs::create_dir_all(root.join("store/1.12.2")).unwrap();
+        fs::write(root.join("store/1.12.2/1.12.2.json"),
+                  r#"{ "id": "1.12.2", "type": "release", "time": "", "releaseTime": "" }"#).unwrap();
+        let manager = VersionManager::new(root.join("versions").as_path())
+            .with_source(Box::new(LocalVersionSource::new(root.join("store").as_path())));
+        assert!(manager.version_of("1.12.2").is_err());
+        assert!(!root.join("versions/1.12.2/1.12.2.json").is_file());
+        assert_eq!(manager.install_from_source("1.12.2").unwrap().id(), "1.12.2");
+        assert_eq!(fs::read_to_string(root.join("versions/1.12.2/1.12.2.json")).unwrap(),
+                   r#"{ "id": "1.12.2", "type": "release", "time": "", "releaseTime": "" }"#);
+        assert!(manager.install_from_source("1.7.10").is_err());
+    }
+
+    #[test]
+    fn installed_and_repair() {
+        let root = TempDir::new("installed-and-repair");
+        let manager = VersionManager::new(root.join("versions").as_path());
+        let (libraries, assets) = (root.join("libraries"), root.join("assets"));
+        fs::create_dir_all(root.join("versions/1.12.2")).unwrap();
+        fs::create_dir_all(assets.join("indexes")).unwrap();
+        fs::write(root.join("versions/1.12.2/1.12.2.json"), r#"{
+            "id": "1.12.2", "type": "release", "time": "", "releaseTime": "", "assets": "1.12",
+            "downloads": { "client": { "url": "https://example.com/client.jar", "size": 3,
+                                       "sha1": "a9993e364706816aba3e25717850c26c9cd0d89d" } }
+        }"#).unwrap();
+        assert!(!manager.is_installed("1.12.2"));
+        assert!(!manager.is_installed("1.7.10"));
+        fs::write(root.join("versions/1.12.2/1.12.2.jar"), "abc").unwrap();
+        assert!(manager.is_installed("1.12.2"));
+        assert!(manager.needs_repair("1.12.2", libraries.as_path(), assets.as_path()).unwrap());
+        fs::write(assets.join("indexes/1.12.json"), r#"{ "objects": {} }"#).unwrap();
+        assert!(!manager.needs_repair("1.12.2", libraries.as_path(), assets.as_path()).unwrap());
+        fs::write(root.join("versions/1.12.2/1.12.2.jar"), "abd").unwrap();
+        assert!(manager.needs_repair("1.12.2", libraries.as_path(), assets.as_path()).unwrap());
+    }
+
+    #[test]
+    fn legacy_assets() {
+        let root = TempDir::new("legacy-assets");
+        let (assets, game) = (root.join("assets"), root.join("game"));
+        let manager = VersionManager::new(root.join("versions").as_path());
+        let version: MinecraftVersion = serde_json::from_str(r#"{
+            "id": "1.6.4", "type": "release", "time": "", "releaseTime": "", "assets": "legacy"
+        }"#).unwrap();
+        assert_eq!(version.legacy_assets_dir(&manager, assets.as_path(), game.as_path()), Some(assets.join("virtual/legacy")));
+        fs::create_dir_all(assets.join("indexes")).unwrap();
+        fs::create_dir_all(assets.join("objects/a9")).unwrap();
+        fs::write(assets.join("objects/a9/a9993e364706816aba3e25717850c26c9cd0d89d"), "abc").unwrap();
+        fs::write(assets.join("indexes/legacy.json"), r#"{ "map_to_resources": true, "objects": {
+            "sound/step/grass1.ogg": { "hash": "a9993e364706816aba3e25717850c26c9cd0d89d", "size": 3 }
+        } }"#).unwrap();
+        assert_eq!(version.legacy_assets_dir(&manager, assets.as_path(), game.as_path()), Some(game.join("resources")));
+        assert_eq!(version.materialize_legacy_assets(&manager, assets.as_path(), game.as_path()).unwrap(), Some(game.join("resources")));
+        assert_eq!(fs::read_to_string(game.join("resources/sound/step/grass1.ogg")).unwrap(), "abc");
+        fs::write(assets.join("indexes/legacy.json"), r#"{ "objects": {} }"#).unwrap();
+        assert_eq!(version.legacy_assets_dir(&manager, assets.as_path(), game.as_path()), None);
+    }
+
+    #[test]
+    fn missing_assets() {
+        let root = TempDir::new("missing-assets");
+        fs::create_dir_all(root.join("objects/a9")).unwrap();
+        fs::write(root.join("objects/a9/a9993e364706816aba3e25717850c26c9cd0d89d"), "abc").unwrap();
+        let index: AssetIndex = serde_json::from_str(r#"{ "objects": {
+            "present": { "hash": "a9993e364706816aba3e25717850c26c9cd0d89d", "size": 3 },
+            "duplicate": { "hash": "a9993e364706816aba3e25717850c26c9cd0d89d", "size": 3 },
+            "missing": { "hash": "0000000000000000000000000000000000000000", "size": 3 }
+        } }"#).unwrap();
+        let missing = missing_asset_objects(&index, root.as_path()).unwrap();
+        assert_eq!(missing.iter().map(AssetObject::hash).collect::<Vec<_>>(), vec!["0000000000000000000000000000000000000000"]);
+        fs::write(root.join("objects/a9/a9993e364706816aba3e25717850c26c9cd0d89d"), "abd").unwrap();
+        assert_eq!(missing_asset_objects(&index, root.as_path()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn asset_index_checksum() {
+        let asset_index: AssetDownloadInfo = serde_json::from_str(r#"{ "id": "1.12", "sha1": "1584b57c1d0aaf2a0c3c5ac1ddc8c8e2ad5e9d6d",
+            "size": 143138, "totalSize": 129336389, "url": "https://launchermeta.mojang.com/v1/packages/1.12.json" }"#).unwrap();
+        assert_eq!(asset_index.sha1(), Some("1584b57c1d0aaf2a0c3c5ac1ddc8c8e2ad5e9d6d"));
+        let info = DownloadInfo::from(asset_index);
+        assert_eq!(info.sha1(), Some("1584b57c1d0aaf2a0c3c5ac1ddc8c8e2ad5e9d6d"));
+        assert_eq!(info.size(), Some(143138));
+        let info = DownloadInfo::from(AssetDownloadInfo::new("legacy".to_owned()));
+        assert!(info.checksum().is_none());
+    }
+
+    #[test]
+    fn legacy_asset_index_mirrored() {
+        let info = DownloadInfo::from(AssetDownloadInfo::new("legacy".to_owned()));
+        assert_eq!(info.url(), "https://s3.amazonaws.com/Minecraft.Download/indexes/legacy.json");
+        let mirror = mirrors::MirrorConfig::official().legacy_assets("https://mirror.example.com/legacy");
+        assert_eq!(info.with_mirror(&mirror).url(), "https://mirror.example.com/legacy/indexes/legacy.json");
+        let mirror = mirrors::MirrorConfig::bmclapi();
+        assert_eq!(mirror.rewrite("https://launchermeta.mojang.com/v1/packages/1.12.json"),
+                   "https://bmclapi2.bangbang93.com/v1/packages/1.12.json");
+    }
+
+    #[test]
+    fn download_info_checksums() {
+        let info: DownloadInfo = serde_json::from_str(r#"{ "size": 3, "url": "https://example.com/a.jar",
+            "sha256": "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad" }"#).unwrap();
+        assert_eq!(info.checksum().map(Checksum::algo), Some(HashAlgo::Sha256));
+        assert_eq!(info.sha1(), None);
+        assert_eq!(info.known_size(), Some(3));
+        assert_eq!(serde_json::to_value(&info).unwrap()["sha256"], json!("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"));
+        let json = json!({ "size": 3, "url": "https://example.com/a.jar", "sha1": "a9993e364706816aba3e25717850c26c9cd0d89d",
+                           "sha256": "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad" });
+        let info: DownloadInfo = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(info.sha1(), Some("a9993e364706816aba3e25717850c26c9cd0d89d"));
+        assert_eq!(serde_json::to_value(&info).unwrap(), json);
+        let info: DownloadInfo = serde_json::from_str(r#"{ "url": "https://example.com/b.jar" }"#).unwrap();
+        assert!(info.checksum().is_none());
+        assert_eq!(info.known_size(), None);
+    }
+
+    #[test]
+    fn version_manifest() {
+        let manifest: VersionManifest = serde_json::from_str(r#"{
+            "latest": { "release": "1.12.2", "snapshot": "17w50a" },
+            "versions": [
+                { "id": "17w50a", "type": "snapshot", "url": "https://example.com/17w50a.json",
+                  "time": "", "releaseTime": "" },
+                { "id": "1.12.2", "type": "release", "url": "https://example.com/1.12.2.json",
+                  "time": "", "releaseTime": "", "sha1": "0123456789abcdef" },
+                { "id": "b1.7.3", "type": "old_beta", "url": "https://example.com/b1.7.3.json",
+                  "time": "", "releaseTime": "" },
+                { "id": "24w14potato", "type": "pending", "url": "https://example.com/24w14potato.json",
+                  "time": "", "releaseTime": "" }
+            ]
+        }"#).unwrap();
+        assert_eq!(manifest.latest(VersionType::Release).map(|v| v.id()), Some("1.12.2"));
+        assert_eq!(manifest.latest(VersionType::Snapshot).map(|v| v.url()), Some("https://example.com/17w50a.json"));
+        assert_eq!(manifest.get("1.12.2").and_then(|v| v.sha1()), Some("0123456789abcdef"));
+        let old_betas = manifest.filter_by_type(VersionType::OldBeta);
+        assert_eq!(old_betas.iter().map(|v| v.id()).collect::<Vec<_>>(), vec!["b1.7.3"]);
+        assert_eq!(manifest.get("24w14potato").map(|v| v.version_type()), Some(VersionType::Unknown));
+    }
+
+    #[test]
+    fn library_rules() {
+        let library: Library = serde_json::from_str(r#"{
+            "name": "ca.weblite:java-objc-bridge:1.0.0",
+            "rules": [ { "action": "allow", "os": { "name": "osx" } } ]
+        }"#).unwrap();
+        assert!(library.is_allowed_on("64", "macos"));
+        assert!(!library.is_allowed_on("64", "linux"));
+        assert_eq!(library.explain_rules("64", "linux").to_string(), "disallowed by rule allow os.name=osx");
+        assert!(!library.is_allowed_on("64", "windows"));
+        let library: Library = serde_json::from_str(r#"{
+            "name": "org.lwjgl.lwjgl:lwjgl:2.9.4",
+            "rules": [ { "action": "allow" }, { "action": "disallow", "os": { "name": "osx" } } ]
+        }"#).unwrap();
+        assert!(library.is_allowed_on("64", "linux"));
+        assert!(!library.is_allowed_on("64", "osx"));
+        let library: Library = serde_json::from_str(r#"{
+            "name": "com.example:x86-only:1.0",
+            "rules": [ { "action": "allow", "os": { "name": "windows", "arch": "x86" } } ]
+        }"#).unwrap();
+        assert!(library.is_allowed_on("32", "windows"));
+        assert!(!library.is_allowed_on("64", "windows"));
+    }
+
+    #[test]
+    fn forge_round_trip() {
+        let json = r#"{
+            "_comment_": [
+                "Please do not automate the download and installation of Forge.",
+                "Our efforts are supported by ads from the download page."
+            ],
+            "id": "1.12.2-forge-14.23.5.2859",
+            "time": "2019-08-31T16:59:06+0000",
+            "releaseTime": "1960-01-01T00:00:00-0700",
+            "type": "release",
+            "minecraftArguments": "--username ${auth_player_name} --version ${version_name} --gameDir ${game_directory} --assetsDir ${assets_root} --assetIndex ${assets_index_name} --uuid ${auth_uuid} --accessToken ${auth_access_token} --userType ${user_type} --tweakClass net.minecraftforge.fml.common.launcher.FMLTweaker --versionType Forge",
+            "mainClass": "net.minecraft.launchwrapper.Launch",
+            "inheritsFrom": "1.12.2",
+            "jar": "1.12.2",
+            "logging": {},
+            "libraries": [
+                {
+                    "name": "net.minecraftforge:forge:1.12.2-14.23.5.2859",
+                    "downloads": {
+                        "artifact": {
+                            "path": "net/minecraftforge/forge/1.12.2-14.23.5.2859/forge-1.12.2-14.23.5.2859.jar",
+                            "url": "",
+                            "sha1": "f4adc1f1e4cd1d1d4be7d8e5e0c8b9a6b4d3c2e1",
+                            "size": 4466802
+                        }
+                    }
+                },
+                {
+                    "name": "net.minecraft:launchwrapper:1.12",
+                    "serverreq": true
+                },
+                {
+                    "name": "org.scala-lang:scala-library:2.11.1",
+                    "url": "http://files.minecraftforge.net/maven/",
+                    "checksums": [ "0a57044d10895f8d3dd66ad4286891f607169d948" ],
+                    "serverreq": true,
+                    "clientreq": true
+                }
+            ]
+        }"#;
+        let version: MinecraftVersion = serde_json::from_str(json).unwrap();
+        let expected: Value = serde_json::from_str(json).unwrap();
+        assert_eq!(serde_json::to_value(&version).unwrap(), expected);
+    }
+
+    #[test]
+    fn extract_natives() {
+        use std::io::Write;
+        use zip::write::{FileOptions, ZipWriter};
+        let root = TempDir::new("extract-natives");
+        fs::create_dir_all(root.as_path()).unwrap();
+        let mut jars = Vec::new();
+        for &(name, contents) in [("a.jar", "first"), ("b.jar", "second")].iter() {
+            let mut zip = ZipWriter::new(fs::File::create(root.join(name)).unwrap());
+            for entry in ["META-INF/MANIFEST.MF", "liblwjgl.so", "linux/x64/libglfw.so"].iter() {
+                zip.start_file(*entry, FileOptions::default()).unwrap();
+                zip.write_all(contents.as_bytes()).unwrap();
+            }
+            zip.finish().unwrap();
+            jars.push(NativeJar { path: root.join(name), exclude: vec!["META-INF/".to_owned()], flatten: true });
+        }
+        let target = root.join("natives");
+        let collection = NativeCollection::from_jars(jars);
+        assert_eq!(collection.source_jars(), vec![root.join("a.jar").as_path(), root.join("b.jar").as_path()]);
+        let mut extracted = collection.extract_to(target.as_path()).unwrap();
+        extracted.sort();
+        assert_eq!(extracted, vec!["liblwjgl.so", "linux/x64/libglfw.so"]);
+        assert_eq!(fs::read_to_string(target.join("liblwjgl.so")).unwrap(), "second");
+        assert_eq!(fs::read_to_string(target.join("libglfw.so")).unwrap(), "second");
+    }
+
+    #[test]
+    fn bundled_server() {
+        use std::io::Write;
+        use zip::write::{FileOptions, ZipWriter};
+        let root = TempDir::new("bundled-server");
+        fs::create_dir_all(root.as_path()).unwrap();
+        for &(name, main_class) in [("bundler.jar", "net.minecraft.bundler.Main"), ("server.jar", "net.minecraft.server.MinecraftServer")].iter() {
+            let mut zip = ZipWriter::new(fs::File::create(root.join(name)).unwrap());
+            zip.start_file("META-INF/MANIFEST.MF", FileOptions::default()).unwrap();
+            zip.write_all(format!("Manifest-Version: 1.0\r\nMain-Class: {}\r\n", main_class).as_bytes()).unwrap();
+            zip.finish().unwrap();
+        }
+        assert!(is_bundled_server(root.join("bundler.jar").as_path()).unwrap());
+        assert!(!is_bundled_server(root.join("server.jar").as_path()).unwrap());
+    }
+
+    #[test]
+    fn resolution_arguments() {
+        let legacy = r#"{
+            "id": "1.8.9", "type": "release", "time": "", "releaseTime": "",
+            "minecraftArguments": "--username ${auth_player_name}"
+        }"#;
+        let modern = r#"{
+            "id": "1.19", "type": "release", "time": "", "releaseTime": "",
+            "arguments": {
+                "game": [
+                    "--username", "${auth_player_name}",
+                    { "rules": [{ "action": "allow", "features": { "has_custom_resolution": true } }],
+                      "value": ["--width", "${resolution_width}", "--height", "${resolution_height}"] }
+                ],
+                "jvm": []
+            }
+        }"#;
+        let enabled = FeatureSet::new().with("has_custom_resolution", true);
+        let disabled = FeatureSet::new();
+        let expected = vec!["--username <auth_player_name>", "--width 1280", "--height 720"];
+        assert_eq!(game_arguments(legacy, &enabled), expected);
+        assert_eq!(game_arguments(modern, &enabled), expected);
+        assert_eq!(game_arguments(legacy, &disabled), vec!["--username <auth_player_name>"]);
+        assert_eq!(game_arguments(modern, &disabled), vec!["--username <auth_player_name>"]);
+    }
+
+    #[test]
+    fn empty_argument_tokens() {
+        let legacy = r#"{
+            "id": "1.8.9", "type": "release", "time": "", "releaseTime": "",
+            "minecraftArguments": "--username ${auth_player_name}  '' --version  ${version_name} --tweakClass ''"
+        }"#;
+        assert_eq!(game_arguments(legacy, &FeatureSet::new()),
+                   vec!["--username <auth_player_name>", "--version <version_name>", "--tweakClass "]);
+        let version: MinecraftVersion = serde_json::from_str(legacy).unwrap();
+        let manager = VersionManager::new(Path::new("versions/"));
+        let mut parameters = Vec::new();
+        let strategy = parsing::ParameterStrategy::map(|s| format!("<{}>", s));
+        version.collect_game_arguments(&manager, &mut parameters, &FeatureSet::new(), &strategy).unwrap();
+        assert_eq!(parameters[2].name(), "--tweakClass");
+        assert_eq!(parameters[2].value(), Some(""));
+    }
+
+    #[test]
+    fn extract_lock_released() {
+        let root = TempDir::new("extract-lock-released");
+        let path_buf = root.to_path_buf();
+        let first = ExtractLock::acquire(path_buf.clone());
+        let second = ExtractLock::acquire(path_buf.clone());
+        assert!(Arc::ptr_eq(&first.1, &second.1));
+        drop(first);
+        assert!(EXTRACT_LOCKS.lock().unwrap().contains_key(&path_buf));
+        drop(second);
+        assert!(!EXTRACT_LOCKS.lock().unwrap().contains_key(&path_buf));
+    }
+
+    #[test]
+    fn natives_for_arch() {
+        let names = [
+            "org.lwjgl:lwjgl:3.3.1",
+            "org.lwjgl:lwjgl:3.3.1:natives-windows",
+            "org.lwjgl:lwjgl:3.3.1:natives-windows-x86",
+            "org.lwjgl:lwjgl:3.3.1:natives-windows-arm64",
+            "org.lwjgl:lwjgl-glfw:3.3.1:natives-windows",
+            "org.lwjgl:lwjgl-glfw:3.3.1:natives-windows-x86",
+            "org.lwjgl:lwjgl:3.3.1:natives-linux",
+        ];
+        let libraries = || names.iter().map(|name| {
+            serde_json::from_value::<Library>(json!({ "name": name })).unwrap()
+        }).collect::<Vec<_>>();
+        let selected = |arch| Library::select_natives(libraries(), "windows", arch)
+            .into_iter().map(|lib| lib.name).collect::<Vec<_>>();
+        assert_eq!(selected("arm64"), vec![
+            "org.lwjgl:lwjgl:3.3.1",
+            "org.lwjgl:lwjgl:3.3.1:natives-windows-arm64",
+            "org.lwjgl:lwjgl-glfw:3.3.1:natives-windows",
+        ]);
+        assert_eq!(selected("x86"), vec![
+            "org.lwjgl:lwjgl:3.3.1",
+            "org.lwjgl:lwjgl:3.3.1:natives-windows-x86",
+            "org.lwjgl:lwjgl-glfw:3.3.1:natives-windows-x86",
+        ]);
+        assert_eq!(selected("x86_64"), vec![
+            "org.lwjgl:lwjgl:3.3.1",
+            "org.lwjgl:lwjgl:3.3.1:natives-windows",
+            "org.lwjgl:lwjgl-glfw:3.3.1:natives-windows",
+        ]);
+    }
+
+    #[test]
+    fn required_features() {
+        let version: MinecraftVersion = serde_json::from_str(r#"{
+            "id": "1.20", "type": "release", "time": "", "releaseTime": "",
+            "arguments": {
+                "game": [
+                    "--username", "${auth_player_name}",
+                    { "rules": [{ "action": "allow", "features": { "is_demo_user": true } }], "value": "--demo" },
+                    { "rules": [{ "action": "allow", "features": { "is_quick_play_multiplayer": true } }],
+                      "value": ["--quickPlayMultiplayer", "${quickPlayMultiplayer}"] }
+                ],
+                "jvm": [{ "rules": [{ "action": "allow", "os": { "name": "osx" } }], "value": "-XstartOnFirstThread" }]
+            }
+        }"#).unwrap();
+        let manager = VersionManager::new(Path::new("versions/"));
+        let mut features: Vec<_> = version.required_features(&manager).unwrap().into_iter().collect();
+        features.sort();
+        assert_eq!(features, vec!["is_demo_user", "is_quick_play_multiplayer"]);
+    }
+
+    #[test]
+    fn malformed_arguments() {
+        let version: MinecraftVersion = serde_json::from_str(r#"{
+            "id": "1.8.9", "type": "release", "time": "", "releaseTime": "",
+            "minecraftArguments": "--username ${auth_player_name} --title \"unterminated"
+        }"#).unwrap();
+        let manager = VersionManager::new(Path::new("versions/"));
+        let mut parameters = Vec::new();
+        let strategy = parsing::ParameterStrategy::map(|s| s);
+        match version.collect_game_arguments(&manager, &mut parameters, &FeatureSet::new(), &strategy) {
+            Result::Err(Error::ArgumentParseError(e)) => assert_eq!(e.kind(), parsing::ParseErrorKind::UnterminatedQuote),
+            _ => panic!("expected an argument parse error")
+        }
+    }
+
+    #[test]
+    fn windows_classpath() {
+        let entries = vec![PathBuf::from("C:\\Users\\Steve\\.minecraft\\libraries\\lwjgl.jar"),
+                           PathBuf::from("C:\\Users\\Steve\\.minecraft\\versions\\1.19\\1.19.jar")];
+        assert_eq!(join_classpath(&entries, ";").unwrap(),
+                   "C:\\Users\\Steve\\.minecraft\\libraries\\lwjgl.jar;C:\\Users\\Steve\\.minecraft\\versions\\1.19\\1.19.jar");
+        if cfg!(target_os = "windows") { assert_eq!(CLASSPATH_SEPARATOR, ";") }
+    }
+
+    #[test]
+    fn legacy_jvm_arguments() {
+        let json = r#"{
+            "id": "1.8.9", "type": "release", "time": "", "releaseTime": "",
+            "minecraftArguments": "--username ${auth_player_name} --version ${version_name}"
+        }"#;
+        let mut expected = vec![
+            "-Djava.library.path=<natives_directory>",
+            "-Dminecraft.launcher.brand=<launcher_name>",
+            "-Dminecraft.launcher.version=<launcher_version>",
+            "-Dminecraft.client.jar=<primary_jar>",
+            "-Djava.io.tmpdir=/sandbox/tmp",
+            "-Duser.home=/sandbox",
+            "-cp",
+            "<classpath>",
+        ];
+        if OS_PLATFORM == "windows" {
+            expected.insert(0, "-XX:HeapDumpPath=MojangTricksIntelDriversForPerformance_javaw.exe_minecraft.exe.heapdump");
+        }
+        assert_eq!(jvm_arguments(json), expected);
+    }
+
+    #[test]
+    fn modern_jvm_arguments() {
+        let json = r#"{
+            "id": "1.18.2", "type": "release", "time": "", "releaseTime": "",
+            "arguments": {
+                "game": ["--username", "${auth_player_name}"],
+                "jvm": [
+                    { "rules": [{ "action": "allow", "os": { "name": "unknown" } }], "value": ["-XstartOnFirstThread"] },
+                    { "rules": [{ "action": "allow", "features": { "is_demo_user": true } }], "value": "--demo" },
+                    "-Djava.library.path=${natives_directory}",
+                    "-Dminecraft.launcher.brand=${launcher_name}",
+                    "-Dminecraft.launcher.version=${launcher_version}",
+                    "-cp",
+                    "${classpath}"
+                ]
+            }
+        }"#;
+        assert_eq!(jvm_arguments(json), vec![
+            "-Djava.library.path=<natives_directory>",
+            "-Dminecraft.launcher.brand=<launcher_name>",
+            "-Dminecraft.launcher.version=<launcher_version>",
+            "-Djava.io.tmpdir=/sandbox/tmp",
+            "-Duser.home=/sandbox",
+            "-cp",
+            "<classpath>",
+        ]);
+    }
+}
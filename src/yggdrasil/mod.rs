@@ -1,27 +1,71 @@
 #![allow(dead_code)]
 
+use std::error;
 use std::fmt::{self, Display};
-use std::collections::HashMap;
 
-use uuid::{Uuid, NAMESPACE_OID};
+use md5;
+use uuid::Uuid;
 use serde_json;
 
 use requests;
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Property {
+    name: String,
+    value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<String>,
+}
+
+#[derive(Clone, Debug)]
 pub struct Profile {
     uuid: Uuid,
     name: String,
-    properties: HashMap<String, String>,
+    properties: Vec<Property>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UserType {
+    Legacy,
+    Mojang,
+    Msa,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccountKind {
+    Offline,
+    Yggdrasil,
+    Microsoft,
 }
 
 #[derive(Debug)]
 pub struct AuthInfo {
-    access_token: Uuid,
+    access_token: String,
     user_profile: Profile,
+    account_kind: AccountKind,
+    xuid: Option<String>,
+    client_id: Option<String>,
 }
 
-pub struct OfflineAuthenticator(String);
+#[derive(Debug)]
+pub enum AuthError {
+    InvalidCredentials(String),
+    MigratedAccount(String),
+    TooManyRequests,
+    InvalidUsername(String),
+    Network(requests::Error),
+    Other(Box<dyn error::Error + Send + Sync>),
+}
+
+#[derive(Debug)]
+pub enum OfflineAuthError {
+    InvalidUsername(String),
+}
+
+pub struct OfflineAuthenticator {
+    name: String,
+    check_name: bool,
+}
 
 pub struct YggdrasilLoginAuthenticator {
     username: String,
@@ -29,6 +73,14 @@ pub struct YggdrasilLoginAuthenticator {
     client_token: Uuid,
 }
 
+pub struct RefreshAuthenticator {
+    access_token: String,
+    client_token: Uuid,
+    user_profile: Option<Profile>,
+}
+
+pub struct BoxedAuthenticator<A>(A);
+
 pub trait Authenticator {
     type Error;
 
@@ -37,7 +89,7 @@ pub trait Authenticator {
 
 impl Profile {
     #[inline]
-    pub fn new(uuid: Uuid, name: String, properties: HashMap<String, String>) -> Profile {
+    pub fn new(uuid: Uuid, name: String, properties: Vec<Property>) -> Profile {
         Profile { uuid, name, properties }
     }
 
@@ -52,9 +104,61 @@ impl Profile {
     }
 
     #[inline]
-    pub fn properties(&self) -> &HashMap<String, String> {
+    pub fn properties(&self) -> &[Property] {
         &self.properties
     }
+
+    #[inline]
+    pub fn textures(&self) -> Option<&Property> {
+        self.properties.iter().find(|p| p.name == "textures")
+    }
+}
+
+impl Property {
+    #[inline]
+    pub fn new(name: String, value: String, signature: Option<String>) -> Property {
+        Property { name, value, signature }
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[inline]
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    #[inline]
+    pub fn signature(&self) -> Option<&str> {
+        self.signature.as_deref()
+    }
+}
+
+impl UserType {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            UserType::Legacy => "legacy",
+            UserType::Mojang => "mojang",
+            UserType::Msa => "msa",
+        }
+    }
+}
+
+impl AccountKind {
+    #[inline]
+    pub fn is_online(&self) -> bool {
+        *self != AccountKind::Offline
+    }
+
+    pub fn user_type(&self) -> UserType {
+        match *self {
+            AccountKind::Offline => UserType::Legacy,
+            AccountKind::Yggdrasil => UserType::Mojang,
+            AccountKind::Microsoft => UserType::Msa,
+        }
+    }
 }
 
 impl Display for Profile {
@@ -70,12 +174,24 @@ impl Display for Profile {
 
 impl AuthInfo {
     #[inline]
-    pub fn new(access_token: Uuid, user_profile: Profile) -> AuthInfo {
-        AuthInfo { access_token, user_profile }
+    pub fn new(access_token: String, user_profile: Profile, account_kind: AccountKind) -> AuthInfo {
+        AuthInfo { access_token, user_profile, account_kind, xuid: None, client_id: None }
+    }
+
+    #[inline]
+    pub fn with_xuid(mut self, xuid: String) -> AuthInfo {
+        self.xuid = Some(xuid);
+        self
     }
 
     #[inline]
-    pub fn access_token(&self) -> &Uuid {
+    pub fn with_client_id(mut self, client_id: String) -> AuthInfo {
+        self.client_id = Some(client_id);
+        self
+    }
+
+    #[inline]
+    pub fn access_token(&self) -> &str {
         &self.access_token
     }
 
@@ -83,33 +199,154 @@ impl AuthInfo {
     pub fn user_profile(&self) -> &Profile {
         &self.user_profile
     }
+
+    #[inline]
+    pub fn user_type(&self) -> UserType {
+        self.account_kind.user_type()
+    }
+
+    #[inline]
+    pub fn account_kind(&self) -> AccountKind {
+        self.account_kind
+    }
+
+    #[inline]
+    pub fn is_offline(&self) -> bool {
+        !self.account_kind.is_online()
+    }
+
+    #[inline]
+    pub fn xuid(&self) -> Option<&str> {
+        self.xuid.as_deref()
+    }
+
+    #[inline]
+    pub fn client_id(&self) -> Option<&str> {
+        self.client_id.as_deref()
+    }
+}
+
+impl From<requests::Error> for AuthError {
+    fn from(e: requests::Error) -> Self {
+        match e {
+            requests::Error::Http { status: 429, .. } => AuthError::TooManyRequests,
+            requests::Error::Http { error: Some(ref error), error_message: Some(ref message), .. }
+            if error == "ForbiddenOperationException" => if message.to_lowercase().contains("migrated") {
+                AuthError::MigratedAccount(message.clone())
+            } else {
+                AuthError::InvalidCredentials(message.clone())
+            },
+            e => AuthError::Network(e)
+        }
+    }
+}
+
+impl Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AuthError::InvalidCredentials(ref message) => write!(f, "invalid credentials: {}", message),
+            AuthError::MigratedAccount(ref message) => write!(f, "account migrated: {}", message),
+            AuthError::TooManyRequests => write!(f, "too many requests"),
+            AuthError::InvalidUsername(ref name) => write!(f, "invalid username: {:?}", name),
+            AuthError::Network(ref e) => Display::fmt(e, f),
+            AuthError::Other(ref e) => Display::fmt(e, f),
+        }
+    }
+}
+
+impl From<OfflineAuthError> for AuthError {
+    fn from(e: OfflineAuthError) -> Self {
+        match e {
+            OfflineAuthError::InvalidUsername(name) => AuthError::InvalidUsername(name)
+        }
+    }
+}
+
+impl Display for OfflineAuthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OfflineAuthError::InvalidUsername(ref name) => write!(f, "invalid username: {:?}", name),
+        }
+    }
+}
+
+impl error::Error for AuthError {}
+
+impl error::Error for OfflineAuthError {}
+
+impl<A> Authenticator for BoxedAuthenticator<A> where A: Authenticator, A::Error: Into<AuthError> {
+    type Error = AuthError;
+
+    fn auth(&self) -> Result<AuthInfo, AuthError> {
+        self.0.auth().map_err(Into::into)
+    }
 }
 
 impl Authenticator for OfflineAuthenticator {
-    type Error = requests::Error;
+    type Error = OfflineAuthError;
 
-    fn auth(&self) -> Result<AuthInfo, requests::Error> {
-        let access_token = Uuid::new_v4();
-        let uuid = Uuid::new_v5(&NAMESPACE_OID, self.0.as_str());
-        let profile = Profile::new(uuid, self.0.clone(), HashMap::new());
-        Result::Ok(AuthInfo::new(access_token, profile))
+    fn auth(&self) -> Result<AuthInfo, OfflineAuthError> {
+        if self.check_name && !is_valid_username(self.name.as_str()) {
+            return Result::Err(OfflineAuthError::InvalidUsername(self.name.clone()));
+        }
+        let access_token = Uuid::new_v4().simple().to_string();
+        let uuid = offline_uuid(self.name.as_str());
+        let profile = Profile::new(uuid, self.name.clone(), Vec::new());
+        Result::Ok(AuthInfo::new(access_token, profile, AccountKind::Offline))
     }
 }
 
 impl Authenticator for YggdrasilLoginAuthenticator {
-    type Error = requests::Error;
+    type Error = AuthError;
 
-    fn auth(&self) -> Result<AuthInfo, requests::Error> {
+    fn auth(&self) -> Result<AuthInfo, AuthError> {
         let username = self.username.as_str();
         let password = self.password.as_str();
         let (token, profile) = requests::req_authenticate(username, password, &self.client_token)?;
-        Result::Ok(AuthInfo::new(token, profile))
+        Result::Ok(AuthInfo::new(token, profile, AccountKind::Yggdrasil))
+    }
+}
+
+pub fn is_valid_username(name: &str) -> bool {
+    let length = name.chars().count();
+    (3..=16).contains(&length) && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+pub fn offline_uuid(offline_name: &str) -> Uuid {
+    let md5::Digest(mut bytes) = md5::compute(format!("OfflinePlayer:{}", offline_name).as_bytes());
+    bytes[6] = (bytes[6] & 0x0f) | 0x30;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    Uuid::from_bytes(&bytes).unwrap()
+}
+
+impl Authenticator for RefreshAuthenticator {
+    type Error = AuthError;
+
+    fn auth(&self) -> Result<AuthInfo, AuthError> {
+        if let Some(ref profile) = self.user_profile {
+            if requests::req_validate(&self.access_token, &self.client_token)? {
+                return Result::Ok(AuthInfo::new(self.access_token.clone(), profile.clone(), AccountKind::Yggdrasil));
+            }
+        }
+        let (token, profile) = requests::req_refresh(&self.access_token, &self.client_token)?;
+        Result::Ok(AuthInfo::new(token, profile, AccountKind::Yggdrasil))
     }
 }
 
+#[inline]
+pub fn boxed<A>(authenticator: A) -> Box<dyn Authenticator<Error = AuthError>>
+    where A: Authenticator + 'static, A::Error: Into<AuthError> {
+    Box::new(BoxedAuthenticator(authenticator))
+}
+
 #[inline]
 pub fn offline(offline_name: &str) -> OfflineAuthenticator {
-    OfflineAuthenticator(offline_name.to_owned())
+    OfflineAuthenticator { name: offline_name.to_owned(), check_name: true }
+}
+
+#[inline]
+pub fn offline_unchecked(offline_name: &str) -> OfflineAuthenticator {
+    OfflineAuthenticator { name: offline_name.to_owned(), check_name: false }
 }
 
 #[inline]
@@ -123,3 +360,31 @@ pub fn yggdrasil_with_client_token(username: String,
                                    client_token: Uuid) -> YggdrasilLoginAuthenticator {
     YggdrasilLoginAuthenticator { username, password, client_token }
 }
+
+#[inline]
+pub fn refresh(access_token: String, client_token: Uuid) -> RefreshAuthenticator {
+    RefreshAuthenticator { access_token, client_token, user_profile: None }
+}
+
+#[inline]
+pub fn refresh_with_profile(access_token: String,
+                            client_token: Uuid,
+                            user_profile: Profile) -> RefreshAuthenticator {
+    RefreshAuthenticator { access_token, client_token, user_profile: Some(user_profile) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_type_from_account_kind() {
+        let profile = Profile::new(offline_uuid("Steve"), "Steve".to_owned(), Vec::new());
+        let offline = AuthInfo::new("0".to_owned(), profile.clone(), AccountKind::Offline);
+        assert_eq!(offline.user_type(), UserType::Legacy);
+        assert!(offline.is_offline());
+        let microsoft = AuthInfo::new("token".to_owned(), profile, AccountKind::Microsoft);
+        assert_eq!(microsoft.user_type().as_str(), "msa");
+        assert!(!microsoft.is_offline());
+    }
+}